@@ -1,12 +1,19 @@
-use crate::basic_data_types::{parse_c_string, parse_le32_f64};
-use avm1_types as avm1;
-use avm1_types::{FunctionFlags, raw};
-use nom::number::complete::{
-  le_f32 as parse_le_f32, le_i16 as parse_le_i16, le_i32 as parse_le_i32, le_u16 as parse_le_u16, le_u8 as parse_u8,
+use crate::basic_data_types::{
+  parse_c_string, parse_le32_f64, parse_le_f32, parse_le_i16, parse_le_i32, parse_le_u16, parse_u8,
 };
+use crate::diagnostic::Diagnostic;
+use crate::error::{BodyError, ParseError, ParseErrorKind};
+use crate::options::ParseOptions;
+use crate::version::check_action_version;
+use avm1_types as avm1;
+use avm1_types::{raw, FunctionFlags};
+use nom::error::context;
+use nom::number::complete::{le_u16, le_u8};
 use nom::{IResult as NomResult, Needed};
 use std::num::NonZeroUsize;
 
+type BodyResult<'a, T> = NomResult<&'a [u8], T, BodyError>;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct ActionHeader {
   pub code: u8,
@@ -15,7 +22,7 @@ pub struct ActionHeader {
 
 // TODO: Use nom::cond
 pub fn parse_action_header(input: &[u8]) -> NomResult<&[u8], ActionHeader> {
-  match parse_u8(input) {
+  match le_u8(input) {
     Ok((remaining_input, action_code)) => {
       if action_code < 0x80 {
         Ok((
@@ -26,7 +33,7 @@ pub fn parse_action_header(input: &[u8]) -> NomResult<&[u8], ActionHeader> {
           },
         ))
       } else {
-        parse_le_u16(remaining_input).map(|(i, length)| {
+        le_u16(remaining_input).map(|(i, length)| {
           (
             i,
             ActionHeader {
@@ -41,24 +48,24 @@ pub fn parse_action_header(input: &[u8]) -> NomResult<&[u8], ActionHeader> {
   }
 }
 
-pub fn parse_goto_frame_action(input: &[u8]) -> NomResult<&[u8], raw::GotoFrame> {
-  let (input, frame) = parse_le_u16(input)?;
+pub fn parse_goto_frame_action(input: &[u8]) -> BodyResult<'_, raw::GotoFrame> {
+  let (input, frame) = context("frame", parse_le_u16)(input)?;
   Ok((input, raw::GotoFrame { frame }))
 }
 
-pub fn parse_get_url_action(input: &[u8]) -> NomResult<&[u8], raw::GetUrl> {
-  let (input, url) = parse_c_string(input)?;
-  let (input, target) = parse_c_string(input)?;
+pub fn parse_get_url_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::GetUrl> {
+  let (input, url) = context("url", |i| parse_c_string(i, options))(input)?;
+  let (input, target) = context("target", |i| parse_c_string(i, options))(input)?;
   Ok((input, raw::GetUrl { url, target }))
 }
 
-pub fn parse_store_register_action(input: &[u8]) -> NomResult<&[u8], raw::StoreRegister> {
-  let (input, register) = parse_u8(input)?;
+pub fn parse_store_register_action(input: &[u8]) -> BodyResult<'_, raw::StoreRegister> {
+  let (input, register) = context("register", parse_u8)(input)?;
   Ok((input, raw::StoreRegister { register }))
 }
 
-pub fn parse_strict_mode_action(input: &[u8]) -> NomResult<&[u8], raw::StrictMode> {
-  let (input, is_strict) = parse_u8(input)?;
+pub fn parse_strict_mode_action(input: &[u8]) -> BodyResult<'_, raw::StrictMode> {
+  let (input, is_strict) = context("is_strict", parse_u8)(input)?;
   Ok((
     input,
     raw::StrictMode {
@@ -67,67 +74,61 @@ pub fn parse_strict_mode_action(input: &[u8]) -> NomResult<&[u8], raw::StrictMod
   ))
 }
 
-pub fn parse_constant_pool_action(input: &[u8]) -> NomResult<&[u8], raw::ConstantPool> {
+pub fn parse_constant_pool_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::ConstantPool> {
   use nom::multi::count;
-  let (input, const_count) = parse_le_u16(input)?;
-  let (input, pool) = count(parse_c_string, usize::from(const_count))(input)?;
+  let (input, const_count) = context("pool.len", parse_le_u16)(input)?;
+  let (input, pool) = context("pool", count(|i| parse_c_string(i, options), usize::from(const_count)))(input)?;
   Ok((input, raw::ConstantPool { pool }))
 }
 
-pub fn parse_wait_for_frame_action(input: &[u8]) -> NomResult<&[u8], raw::WaitForFrame> {
-  let (input, frame) = parse_le_u16(input)?;
-  let (input, skip) = parse_u8(input)?;
+pub fn parse_wait_for_frame_action(input: &[u8]) -> BodyResult<'_, raw::WaitForFrame> {
+  let (input, frame) = context("frame", parse_le_u16)(input)?;
+  let (input, skip) = context("skip", parse_u8)(input)?;
   Ok((input, raw::WaitForFrame { frame, skip }))
 }
 
-pub fn parse_set_target_action(input: &[u8]) -> NomResult<&[u8], raw::SetTarget> {
-  let (input, target_name) = parse_c_string(input)?;
+pub fn parse_set_target_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::SetTarget> {
+  let (input, target_name) = context("target_name", |i| parse_c_string(i, options))(input)?;
   Ok((input, raw::SetTarget { target_name }))
 }
 
-pub fn parse_goto_label_action(input: &[u8]) -> NomResult<&[u8], raw::GoToLabel> {
-  let (input, label) = parse_c_string(input)?;
+pub fn parse_goto_label_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::GoToLabel> {
+  let (input, label) = context("label", |i| parse_c_string(i, options))(input)?;
   Ok((input, raw::GoToLabel { label }))
 }
 
-pub fn parse_wait_for_frame2_action(input: &[u8]) -> NomResult<&[u8], raw::WaitForFrame2> {
-  let (input, skip) = parse_u8(input)?;
+pub fn parse_wait_for_frame2_action(input: &[u8]) -> BodyResult<'_, raw::WaitForFrame2> {
+  let (input, skip) = context("skip", parse_u8)(input)?;
   Ok((input, raw::WaitForFrame2 { skip }))
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct DefineFunction2Flags {
-  pub preload_parent: bool,
-  pub preload_root: bool,
-  pub suppress_super: bool,
-  pub preload_super: bool,
-  pub suppress_arguments: bool,
-  pub preload_arguments: bool,
-  pub suppress_this: bool,
-  pub preload_this: bool,
-  pub preload_global: bool,
-}
-
-// TODO(demurgos): registerCount
+// `register_count` is not checked here: it is validated against the register usage of the
+// function body by `register_usage`.
 
-pub fn parse_define_function2_action(input: &[u8]) -> NomResult<&[u8], raw::DefineFunction2> {
+pub fn parse_define_function2_action<'a>(
+  input: &'a [u8],
+  options: &ParseOptions,
+) -> BodyResult<'a, raw::DefineFunction2> {
   use nom::multi::count;
 
-  let (input, name) = parse_c_string(input)?;
-  let (input, parameter_count) = parse_le_u16(input)?;
-  let (input, register_count) = parse_u8(input)?;
+  let (input, name) = context("name", |i| parse_c_string(i, options))(input)?;
+  let (input, parameter_count) = context("parameters.len", parse_le_u16)(input)?;
+  let (input, register_count) = context("register_count", parse_u8)(input)?;
 
-  let (input, flag_bits) = parse_le_u16(input)?;
+  let (input, flag_bits) = context("flags", parse_le_u16)(input)?;
   let flags = FunctionFlags::from_bits_truncate(flag_bits);
 
-  let (input, parameters) = count(parse_parameter, usize::from(parameter_count))(input)?;
-  fn parse_parameter(input: &[u8]) -> NomResult<&[u8], avm1::Parameter> {
+  let (input, parameters) = context(
+    "parameters",
+    count(|i| parse_parameter(i, options), usize::from(parameter_count)),
+  )(input)?;
+  fn parse_parameter<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, avm1::Parameter> {
     let (input, register) = parse_u8(input)?;
-    let (input, name) = parse_c_string(input)?;
+    let (input, name) = parse_c_string(input, options)?;
     Ok((input, avm1::Parameter { register, name }))
   }
 
-  let (input, body_size) = parse_le_u16(input)?;
+  let (input, body_size) = context("body_size", parse_le_u16)(input)?;
 
   Ok((
     input,
@@ -141,24 +142,28 @@ pub fn parse_define_function2_action(input: &[u8]) -> NomResult<&[u8], raw::Defi
   ))
 }
 
-pub fn parse_try_action(input: &[u8]) -> NomResult<&[u8], raw::Try> {
-  let (input, flags) = parse_u8(input)?;
+pub fn parse_try_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::Try> {
+  let (input, flags) = context("flags", parse_u8)(input)?;
   let has_catch_block = (flags & (1 << 0)) != 0;
   let has_finally_block = (flags & (1 << 1)) != 0;
   let catch_in_register = (flags & (1 << 2)) != 0;
   // Skip bits [3, 7]
 
-  let (input, r#try) = parse_le_u16(input)?;
-  let (input, catch_size) = parse_le_u16(input)?;
-  let (input, finally_size) = parse_le_u16(input)?;
+  let (input, r#try) = context("try", parse_le_u16)(input)?;
+  let (input, catch_size) = context("catch.size", parse_le_u16)(input)?;
+  let (input, finally_size) = context("finally", parse_le_u16)(input)?;
 
-  let (input, catch_target) = parse_catch_target(input, catch_in_register)?;
-  fn parse_catch_target(input: &[u8], catch_in_register: bool) -> NomResult<&[u8], avm1::CatchTarget> {
+  let (input, catch_target) = context("catch.target", |i| parse_catch_target(i, catch_in_register, options))(input)?;
+  fn parse_catch_target<'a>(
+    input: &'a [u8],
+    catch_in_register: bool,
+    options: &ParseOptions,
+  ) -> BodyResult<'a, avm1::CatchTarget> {
     use nom::combinator::map;
     if catch_in_register {
       map(parse_u8, avm1::CatchTarget::Register)(input)
     } else {
-      map(parse_c_string, avm1::CatchTarget::Variable)(input)
+      map(|i| parse_c_string(i, options), avm1::CatchTarget::Variable)(input)
     }
   }
 
@@ -176,27 +181,52 @@ pub fn parse_try_action(input: &[u8]) -> NomResult<&[u8], raw::Try> {
   Ok((input, raw::Try { r#try, catch, finally }))
 }
 
-pub fn parse_with_action(input: &[u8]) -> NomResult<&[u8], raw::With> {
-  let (input, size) = parse_le_u16(input)?;
+pub fn parse_with_action(input: &[u8]) -> BodyResult<'_, raw::With> {
+  let (input, size) = context("size", parse_le_u16)(input)?;
 
   Ok((input, raw::With { size }))
 }
 
-pub fn parse_push_action(mut input: &[u8]) -> NomResult<&[u8], raw::Push> {
+pub fn parse_push_action<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, raw::Push> {
+  match parse_partial_push_action(input, options) {
+    (input, push, None) => Ok((input, push)),
+    (_, _, Some(e)) => Err(nom::Err::Error(e)),
+  }
+}
+
+/// Parses the values of a `Push` action until the end of the input or the first invalid value.
+///
+/// Returns the remaining input (starting at the invalid value), the values decoded so far and
+/// the error that stopped the parsing.
+fn parse_partial_push_action<'a>(
+  mut input: &'a [u8],
+  options: &ParseOptions,
+) -> (&'a [u8], raw::Push, Option<BodyError>) {
   let mut values: Vec<avm1::PushValue> = Vec::new();
   while !input.is_empty() {
-    let (next_input, value) = parse_push_value(input)?;
-    values.push(value);
-    input = next_input;
+    match context("values", |i| parse_push_value(i, options))(input) {
+      Ok((next_input, value)) => {
+        values.push(value);
+        input = next_input;
+      }
+      Err(nom::Err::Error(e) | nom::Err::Failure(e)) => return (input, raw::Push { values }, Some(e)),
+      Err(nom::Err::Incomplete(_)) => {
+        let e = BodyError {
+          field: Some("values"),
+          ..BodyError::missing(ParseErrorKind::UnexpectedEnd, input.len(), 1)
+        };
+        return (input, raw::Push { values }, Some(e));
+      }
+    }
   }
-  Ok((input, raw::Push { values }))
+  (input, raw::Push { values }, None)
 }
 
-fn parse_push_value(input: &[u8]) -> NomResult<&[u8], avm1::PushValue> {
+fn parse_push_value<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, avm1::PushValue> {
   use nom::combinator::map;
   let (input, code) = parse_u8(input)?;
   match code {
-    0 => map(parse_c_string, avm1::PushValue::String)(input),
+    0 => map(|i| parse_c_string(i, options), avm1::PushValue::String)(input),
     1 => map(parse_le_f32, avm1::PushValue::Float32)(input),
     2 => Ok((input, avm1::PushValue::Null)),
     3 => Ok((input, avm1::PushValue::Undefined)),
@@ -206,20 +236,19 @@ fn parse_push_value(input: &[u8]) -> NomResult<&[u8], avm1::PushValue> {
     7 => map(parse_le_i32, avm1::PushValue::Sint32)(input),
     8 => map(parse_u8, |v| avm1::PushValue::Constant(u16::from(v)))(input),
     9 => map(parse_le_u16, avm1::PushValue::Constant)(input),
-    _ => Err(nom::Err::Error(nom::error::Error::new(
-      input,
-      nom::error::ErrorKind::Switch,
-    ))),
+    _ => Err(nom::Err::Error(BodyError::new(ParseErrorKind::UnknownPushValueType(
+      code,
+    )))),
   }
 }
 
-pub fn parse_jump_action(input: &[u8]) -> NomResult<&[u8], raw::Jump> {
-  let (input, offset) = parse_le_i16(input)?;
+pub fn parse_jump_action(input: &[u8]) -> BodyResult<'_, raw::Jump> {
+  let (input, offset) = context("offset", parse_le_i16)(input)?;
   Ok((input, raw::Jump { offset }))
 }
 
-pub fn parse_get_url2_action(input: &[u8]) -> NomResult<&[u8], raw::GetUrl2> {
-  let (input, flags) = parse_u8(input)?;
+pub fn parse_get_url2_action(input: &[u8]) -> BodyResult<'_, raw::GetUrl2> {
+  let (input, flags) = context("flags", parse_u8)(input)?;
   let load_variables = (flags & (1 << 0)) != 0;
   let load_target = (flags & (1 << 1)) != 0;
   // Skip bits [2, 5]
@@ -230,10 +259,10 @@ pub fn parse_get_url2_action(input: &[u8]) -> NomResult<&[u8], raw::GetUrl2> {
     1 => avm1::GetUrl2Method::Get,
     2 => avm1::GetUrl2Method::Post,
     _ => {
-      return Err(nom::Err::Error(nom::error::Error::new(
-        input,
-        nom::error::ErrorKind::Switch,
-      )))
+      return Err(nom::Err::Error(BodyError {
+        field: Some("method"),
+        ..BodyError::new(ParseErrorKind::UnknownGetUrl2Method(method_code))
+      }))
     }
   };
 
@@ -247,12 +276,15 @@ pub fn parse_get_url2_action(input: &[u8]) -> NomResult<&[u8], raw::GetUrl2> {
   ))
 }
 
-pub fn parse_define_function_action(input: &[u8]) -> NomResult<&[u8], raw::DefineFunction> {
+pub fn parse_define_function_action<'a>(
+  input: &'a [u8],
+  options: &ParseOptions,
+) -> BodyResult<'a, raw::DefineFunction> {
   use nom::multi::count;
-  let (input, name) = parse_c_string(input)?;
-  let (input, param_count) = parse_le_u16(input)?;
-  let (input, parameters) = count(parse_c_string, param_count.into())(input)?;
-  let (input, body_size) = parse_le_u16(input)?;
+  let (input, name) = context("name", |i| parse_c_string(i, options))(input)?;
+  let (input, param_count) = context("parameters.len", parse_le_u16)(input)?;
+  let (input, parameters) = context("parameters", count(|i| parse_c_string(i, options), param_count.into()))(input)?;
+  let (input, body_size) = context("body_size", parse_le_u16)(input)?;
 
   Ok((
     input,
@@ -264,18 +296,18 @@ pub fn parse_define_function_action(input: &[u8]) -> NomResult<&[u8], raw::Defin
   ))
 }
 
-pub fn parse_if_action(input: &[u8]) -> NomResult<&[u8], raw::If> {
-  let (input, offset) = parse_le_i16(input)?;
+pub fn parse_if_action(input: &[u8]) -> BodyResult<'_, raw::If> {
+  let (input, offset) = context("offset", parse_le_i16)(input)?;
   Ok((input, raw::If { offset }))
 }
 
-pub fn parse_goto_frame2_action(input: &[u8]) -> NomResult<&[u8], raw::GotoFrame2> {
+pub fn parse_goto_frame2_action(input: &[u8]) -> BodyResult<'_, raw::GotoFrame2> {
   use nom::combinator::cond;
-  let (input, flags) = parse_u8(input)?;
+  let (input, flags) = context("flags", parse_u8)(input)?;
   let play = (flags & (1 << 0)) != 0;
   let has_scene_bias = (flags & (1 << 1)) != 0;
   // Skip bits [2, 7]
-  let (input, scene_bias) = cond(has_scene_bias, parse_le_u16)(input)?;
+  let (input, scene_bias) = context("scene_bias", cond(has_scene_bias, parse_le_u16))(input)?;
 
   Ok((
     input,
@@ -288,6 +320,84 @@ pub fn parse_goto_frame2_action(input: &[u8]) -> NomResult<&[u8], raw::GotoFrame
 
 // TODO: Return `(&[u8], ast::Action)` (the function should never fail)
 pub fn parse_action(input: &[u8]) -> NomResult<&[u8], raw::Action> {
+  parse_action_with(input, &ParseOptions::default())
+}
+
+pub fn parse_action_with<'a>(input: &'a [u8], options: &ParseOptions) -> NomResult<&'a [u8], raw::Action> {
+  let (input, decoded) = parse_decoded_action(input, 0, options)?;
+  Ok((input, decoded.action))
+}
+
+/// Parses an action, returning the cause of the error instead of an `Error` action when its body
+/// is invalid.
+///
+/// Truncated actions are reported like `parse_action_with`.
+pub fn parse_action_detailed<'a>(
+  input: &'a [u8],
+  options: &ParseOptions,
+) -> NomResult<&'a [u8], Result<raw::Action, ParseError>> {
+  let (input, decoded) = parse_decoded_action(input, 0, options)?;
+  let result = match decoded.error {
+    Some(error) => Err(error),
+    None => Ok(decoded.action),
+  };
+  Ok((input, result))
+}
+
+/// Bits and bytes of an encoded action that are not represented in `raw::Action`.
+///
+/// The Flash Player ignores them, but they are required to re-emit the original bytes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActionTrivia {
+  /// Reserved bits of the flags field, at their original position; `0` for actions without
+  /// reserved bits (only `DefineFunction2`, `GetUrl2`, `GotoFrame2` and `Try` have some).
+  pub reserved_bits: u16,
+  /// Bytes of the action body that are not part of its fields.
+  ///
+  /// These are the bytes following the last field, or the whole body for `Error` actions.
+  pub trailing_bytes: Vec<u8>,
+}
+
+/// Parses an action, also returning the bits and bytes ignored by `parse_action_with`.
+pub fn parse_action_lossless<'a>(
+  input: &'a [u8],
+  options: &ParseOptions,
+) -> NomResult<&'a [u8], (raw::Action, ActionTrivia)> {
+  let (input, decoded) = parse_decoded_action(input, 0, options)?;
+  let trivia = decoded.trivia();
+  Ok((input, (decoded.action, trivia)))
+}
+
+/// Action with the raw bytes of its body and the diagnostics found while parsing it
+pub(crate) struct DecodedAction<'a> {
+  code: u8,
+  body: &'a [u8],
+  /// Bytes of `body` not represented in `action`
+  trailing: &'a [u8],
+  pub(crate) action: raw::Action,
+  /// Cause of the error, if `action` is an `Error` action
+  pub(crate) error: Option<ParseError>,
+  pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl DecodedAction<'_> {
+  pub(crate) fn trivia(&self) -> ActionTrivia {
+    let reserved_bits = match self.action {
+      raw::Action::Error(_) => 0,
+      _ => parse_reserved_bits(self.code, self.body),
+    };
+    ActionTrivia {
+      reserved_bits,
+      trailing_bytes: self.trailing.to_vec(),
+    }
+  }
+}
+
+pub(crate) fn parse_decoded_action<'a>(
+  input: &'a [u8],
+  offset: usize,
+  options: &ParseOptions,
+) -> NomResult<&'a [u8], DecodedAction<'a>> {
   let base_input = input; // Keep original input to compute lengths.
 
   let (input, header) = parse_action_header(input)?;
@@ -301,11 +411,114 @@ pub fn parse_action(input: &[u8]) -> NomResult<&[u8], raw::Action> {
     )));
   }
   let (action_body, input) = input.split_at(body_len);
-  let action = parse_action_body(action_body, header.code);
-  Ok((input, action))
+  let mut diagnostics: Vec<Diagnostic> = Vec::new();
+  let body_result = parse_action_body(action_body, header.code, options);
+  let mismatch: Option<(usize, usize)> = match &body_result {
+    Ok((rest, _)) if !rest.is_empty() => Some((body_len - rest.len(), 0)),
+    Err(e)
+      if matches!(
+        e.kind,
+        ParseErrorKind::UnexpectedEnd | ParseErrorKind::UnterminatedString
+      ) =>
+    {
+      Some((body_len - e.remaining, e.needed))
+    }
+    _ => None,
+  };
+  if let Some((consumed, needed)) = mismatch {
+    diagnostics.push(Diagnostic::LengthMismatch {
+      offset,
+      code: header.code,
+      declared: body_len,
+      consumed,
+      needed,
+    });
+  }
+  let (trailing, action) = match body_result {
+    Ok((rest, action)) if rest.is_empty() || !options.strict => (rest, Ok(action)),
+    Ok((rest, _)) => {
+      let e = BodyError::new(ParseErrorKind::TrailingBytes(rest.len()));
+      (action_body, Err(e.at(offset, header.code)))
+    }
+    Err(e)
+      if header.code == 0x96 && options.recover_push && matches!(e.kind, ParseErrorKind::UnknownPushValueType(_)) =>
+    {
+      let (tail, push, _) = parse_partial_push_action(action_body, options);
+      diagnostics.push(Diagnostic::PartialPush {
+        error: e.at(offset, header.code),
+        values: push.values.len(),
+        tail: tail.to_vec(),
+      });
+      (tail, Ok(raw::Action::Push(push)))
+    }
+    Err(e) => (action_body, Err(e.at(offset, header.code))),
+  };
+  let (action, error) = match action {
+    Ok(action) => (action, None),
+    Err(e) => (raw::Action::Error(e.into()), Some(e)),
+  };
+  if let (true, Some(swf_version)) = (options.check_versions, options.swf_version) {
+    diagnostics.extend(check_action_version(offset, header.code, swf_version));
+  }
+  Ok((
+    input,
+    DecodedAction {
+      code: header.code,
+      body: action_body,
+      trailing,
+      action,
+      error,
+      diagnostics,
+    },
+  ))
+}
+
+/// Returns the reserved bits of the flags field of the action body, or `0` if there are none.
+fn parse_reserved_bits(code: u8, body: &[u8]) -> u16 {
+  match code {
+    // `DefineFunction2`: bits [9, 15], the flags follow the name, parameter count and register count
+    0x8e => {
+      let flags_offset = match body.iter().position(|b| *b == 0) {
+        Some(name_len) => name_len + 1 + 2 + 1,
+        None => return 0,
+      };
+      match parse_le_u16(body.get(flags_offset..).unwrap_or_default()) {
+        Ok((_, flags)) => flags & 0xfe00,
+        Err(_) => 0,
+      }
+    }
+    // `Try`: bits [3, 7]
+    0x8f => body.first().map_or(0, |flags| u16::from(flags & 0xf8)),
+    // `GetUrl2`: bits [2, 5]
+    0x9a => body.first().map_or(0, |flags| u16::from(flags & 0x3c)),
+    // `GotoFrame2`: bits [2, 7]
+    0x9f => body.first().map_or(0, |flags| u16::from(flags & 0xfc)),
+    _ => 0,
+  }
 }
 
-fn parse_action_body(input: &[u8], code: u8) -> raw::Action {
+/// Returns the error for a truncated action starting at `input`: either its header or its body
+/// does not fit in the input.
+pub(crate) fn truncated_action_error(input: &[u8], offset: usize) -> ParseError {
+  let code = input.first().copied().unwrap_or_default();
+  let field = if parse_action_header(input).is_ok() {
+    "body"
+  } else {
+    "length"
+  };
+  ParseError {
+    offset,
+    code,
+    field,
+    kind: ParseErrorKind::UnexpectedEnd,
+  }
+}
+
+fn parse_action_body<'a>(
+  input: &'a [u8],
+  code: u8,
+  options: &ParseOptions,
+) -> Result<(&'a [u8], raw::Action), BodyError> {
   use nom::combinator::map;
   let result = match code {
     0x00 => Ok((input, raw::Action::End)),
@@ -392,21 +605,30 @@ fn parse_action_body(input: &[u8], code: u8) -> raw::Action {
     0x68 => Ok((input, raw::Action::StringGreater)),
     0x69 => Ok((input, raw::Action::Extends)),
     0x81 => map(parse_goto_frame_action, raw::Action::GotoFrame)(input),
-    0x83 => map(parse_get_url_action, |a| raw::Action::GetUrl(Box::new(a)))(input),
+    0x83 => map(
+      |i| parse_get_url_action(i, options),
+      |a| raw::Action::GetUrl(Box::new(a)),
+    )(input),
     0x87 => map(parse_store_register_action, raw::Action::StoreRegister)(input),
-    0x88 => map(parse_constant_pool_action, raw::Action::ConstantPool)(input),
+    0x88 => map(|i| parse_constant_pool_action(i, options), raw::Action::ConstantPool)(input),
     0x89 => map(parse_strict_mode_action, raw::Action::StrictMode)(input),
     0x8a => map(parse_wait_for_frame_action, raw::Action::WaitForFrame)(input),
-    0x8b => map(parse_set_target_action, raw::Action::SetTarget)(input),
-    0x8c => map(parse_goto_label_action, raw::Action::GotoLabel)(input),
+    0x8b => map(|i| parse_set_target_action(i, options), raw::Action::SetTarget)(input),
+    0x8c => map(|i| parse_goto_label_action(i, options), raw::Action::GotoLabel)(input),
     0x8d => map(parse_wait_for_frame2_action, raw::Action::WaitForFrame2)(input),
-    0x8e => map(parse_define_function2_action, |a| raw::Action::DefineFunction2(Box::new(a)))(input),
-    0x8f => map(parse_try_action, |a|raw::Action::Try(Box::new(a)))(input),
+    0x8e => map(
+      |i| parse_define_function2_action(i, options),
+      |a| raw::Action::DefineFunction2(Box::new(a)),
+    )(input),
+    0x8f => map(|i| parse_try_action(i, options), |a| raw::Action::Try(Box::new(a)))(input),
     0x94 => map(parse_with_action, raw::Action::With)(input),
-    0x96 => map(parse_push_action, raw::Action::Push)(input),
+    0x96 => map(|i| parse_push_action(i, options), raw::Action::Push)(input),
     0x99 => map(parse_jump_action, raw::Action::Jump)(input),
     0x9a => map(parse_get_url2_action, raw::Action::GetUrl2)(input),
-    0x9b => map(parse_define_function_action, |a|raw::Action::DefineFunction(Box::new(a)))(input),
+    0x9b => map(
+      |i| parse_define_function_action(i, options),
+      |a| raw::Action::DefineFunction(Box::new(a)),
+    )(input),
     0x9d => map(parse_if_action, raw::Action::If)(input),
     0x9e => Ok((input, raw::Action::Call)),
     0x9f => map(parse_goto_frame2_action, raw::Action::GotoFrame2)(input),
@@ -419,8 +641,9 @@ fn parse_action_body(input: &[u8], code: u8) -> raw::Action {
     )),
   };
   match result {
-    Ok((_, action)) => action,
-    Err(_) => raw::Action::Error(raw::Error { error: None }),
+    Ok(parsed) => Ok(parsed),
+    Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(e),
+    Err(nom::Err::Incomplete(_)) => Err(BodyError::new(ParseErrorKind::UnexpectedEnd)),
   }
 }
 
@@ -432,8 +655,8 @@ mod tests {
   #[test]
   fn test_parse_push_action() {
     {
-      let input = vec![0x04, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x08, 0x02];
-      let actual = parse_push_action(&input[..]);
+      let input = [0x04, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x08, 0x02];
+      let actual = parse_push_action(&input[..], &ParseOptions::default());
       let expected = Ok((
         &[][..],
         raw::Push {
@@ -443,8 +666,8 @@ mod tests {
       assert_eq!(actual, expected);
     }
     {
-      let input = vec![0x00, 0x00];
-      let actual = parse_push_action(&input[..]);
+      let input = [0x00, 0x00];
+      let actual = parse_push_action(&input[..], &ParseOptions::default());
       let expected = Ok((
         &[][..],
         raw::Push {
@@ -454,8 +677,8 @@ mod tests {
       assert_eq!(actual, expected);
     }
     {
-      let input = vec![0x00, 0x01, 0x00];
-      let actual = parse_push_action(&input[..]);
+      let input = [0x00, 0x01, 0x00];
+      let actual = parse_push_action(&input[..], &ParseOptions::default());
       let expected = Ok((
         &[][..],
         raw::Push {
@@ -469,42 +692,42 @@ mod tests {
   #[test]
   fn test_parse_action_header() {
     {
-      let input = vec![0b00000000, 0b00000000, 0b00000000, 0b00000000];
+      let input = [0b00000000, 0b00000000, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((&input[1..], ActionHeader { code: 0x00, length: 0 }))
       );
     }
     {
-      let input = vec![0b00000001, 0b00000000, 0b00000000, 0b00000000];
+      let input = [0b00000001, 0b00000000, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((&input[1..], ActionHeader { code: 0x01, length: 0 }))
       );
     }
     {
-      let input = vec![0b00010000, 0b00000000, 0b00000000, 0b00000000];
+      let input = [0b00010000, 0b00000000, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((&input[1..], ActionHeader { code: 0x10, length: 0 }))
       );
     }
     {
-      let input = vec![0b10000000, 0b00000000, 0b00000000, 0b00000000];
+      let input = [0b10000000, 0b00000000, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((&input[3..], ActionHeader { code: 0x80, length: 0 }))
       );
     }
     {
-      let input = vec![0b10000000, 0b00000001, 0b00000000, 0b00000000];
+      let input = [0b10000000, 0b00000001, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((&input[3..], ActionHeader { code: 0x80, length: 1 }))
       );
     }
     {
-      let input = vec![0b10000000, 0b00000000, 0b00000001, 0b00000000];
+      let input = [0b10000000, 0b00000000, 0b00000001, 0b00000000];
       assert_eq!(
         parse_action_header(&input[..]),
         Ok((
@@ -521,7 +744,7 @@ mod tests {
   #[test]
   fn test_parse_action() {
     {
-      let input = vec![0b00000001, 0b00000000, 0b00000000, 0b00000000];
+      let input = [0b00000001, 0b00000000, 0b00000000, 0b00000000];
       assert_eq!(
         parse_action(&input),
         Ok((
@@ -534,7 +757,7 @@ mod tests {
       );
     }
     {
-      let input = vec![0b10000000, 0b00000001, 0b00000000, 0b00000011];
+      let input = [0b10000000, 0b00000001, 0b00000000, 0b00000011];
       assert_eq!(
         parse_action(&input[..]),
         Ok((
@@ -547,11 +770,272 @@ mod tests {
       );
     }
     {
-      let input = vec![0b10000000, 0b00000010, 0b00000000, 0b00000011];
+      let input = [0b10000000, 0b00000010, 0b00000000, 0b00000011];
       assert_eq!(
         parse_action(&input[..]),
         Err(::nom::Err::Incomplete(nom::Needed::Size(NonZeroUsize::new(5).unwrap())))
       );
     }
   }
+
+  #[test]
+  fn test_parse_action_strict() {
+    let input = [0x87, 0x02, 0x00, 0x01, 0x02];
+    assert_eq!(
+      parse_action(&input[..]),
+      Ok((&[][..], raw::Action::StoreRegister(raw::StoreRegister { register: 1 })))
+    );
+    let options = ParseOptions {
+      strict: true,
+      ..ParseOptions::default()
+    };
+    let expected = ParseError {
+      offset: 0,
+      code: 0x87,
+      field: "body",
+      kind: ParseErrorKind::TrailingBytes(1),
+    };
+    assert_eq!(
+      parse_action_with(&input[..], &options),
+      Ok((&[][..], raw::Action::Error(expected.into())))
+    );
+  }
+
+  #[test]
+  fn test_parse_action_lossless() {
+    {
+      let input = [0x9a, 0x01, 0x00, 0x46];
+      let (_, (action, trivia)) = parse_action_lossless(&input[..], &ParseOptions::default()).unwrap();
+      assert_eq!(
+        action,
+        raw::Action::GetUrl2(raw::GetUrl2 {
+          method: avm1::GetUrl2Method::Get,
+          load_target: true,
+          load_variables: false,
+        })
+      );
+      assert_eq!(
+        trivia,
+        ActionTrivia {
+          reserved_bits: 0x04,
+          trailing_bytes: Vec::new(),
+        }
+      );
+    }
+    {
+      let input = [0x9f, 0x04, 0x00, 0xf1, 0xaa, 0xbb, 0xcc];
+      let (_, (action, trivia)) = parse_action_lossless(&input[..], &ParseOptions::default()).unwrap();
+      assert_eq!(
+        action,
+        raw::Action::GotoFrame2(raw::GotoFrame2 {
+          play: true,
+          scene_bias: 0,
+        })
+      );
+      assert_eq!(
+        trivia,
+        ActionTrivia {
+          reserved_bits: 0xf0,
+          trailing_bytes: vec![0xaa, 0xbb, 0xcc],
+        }
+      );
+    }
+    {
+      let input = [0x8e, 0x09, 0x00, 0x66, 0x00, 0x00, 0x00, 0x02, 0x01, 0x82, 0x00, 0x00];
+      let (_, (_, trivia)) = parse_action_lossless(&input[..], &ParseOptions::default()).unwrap();
+      assert_eq!(trivia.reserved_bits, 0x8200);
+    }
+    {
+      let input = [0x96, 0x02, 0x00, 0x0a, 0x00];
+      let (_, (action, trivia)) = parse_action_lossless(&input[..], &ParseOptions::default()).unwrap();
+      assert!(matches!(action, raw::Action::Error(_)));
+      assert_eq!(
+        trivia,
+        ActionTrivia {
+          reserved_bits: 0,
+          trailing_bytes: vec![0x0a, 0x00],
+        }
+      );
+    }
+  }
+
+  #[test]
+  fn test_parse_action_length_mismatch() {
+    // `StoreRegister` with a 2-byte body, `StoreRegister` with an empty body, `Stop`
+    let input = [0x87, 0x02, 0x00, 0x01, 0xff, 0x87, 0x00, 0x00, 0x07];
+    let options = ParseOptions::default();
+    let (rest, decoded) = parse_decoded_action(&input[..], 0, &options).ok().unwrap();
+    assert_eq!(
+      decoded.action,
+      raw::Action::StoreRegister(raw::StoreRegister { register: 1 })
+    );
+    assert_eq!(
+      decoded.diagnostics,
+      vec![Diagnostic::LengthMismatch {
+        offset: 0,
+        code: 0x87,
+        declared: 2,
+        consumed: 1,
+        needed: 0,
+      }]
+    );
+    let (rest, decoded) = parse_decoded_action(rest, 5, &options).ok().unwrap();
+    assert_eq!(rest, &[0x07]);
+    assert_eq!(
+      decoded.diagnostics,
+      vec![Diagnostic::LengthMismatch {
+        offset: 5,
+        code: 0x87,
+        declared: 0,
+        consumed: 0,
+        needed: 1,
+      }]
+    );
+    let (_, decoded) = parse_decoded_action(rest, 8, &options).ok().unwrap();
+    assert_eq!(decoded.diagnostics, Vec::new());
+
+    // `Push` with a truncated `Sint32` value
+    let input = [0x96, 0x03, 0x00, 0x07, 0x01, 0x00];
+    let (_, decoded) = parse_decoded_action(&input[..], 0, &options).ok().unwrap();
+    assert_eq!(
+      decoded.diagnostics,
+      vec![Diagnostic::LengthMismatch {
+        offset: 0,
+        code: 0x96,
+        declared: 3,
+        consumed: 1,
+        needed: 2,
+      }]
+    );
+  }
+
+  #[test]
+  fn test_parse_action_recover_push() {
+    let input = [0x96, 0x08, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x01, 0x02];
+    let options = ParseOptions {
+      recover_push: true,
+      ..ParseOptions::default()
+    };
+    let (rest, decoded) = parse_decoded_action(&input[..], 0, &options).ok().unwrap();
+    assert_eq!(rest, &[] as &[u8]);
+    assert_eq!(
+      decoded.action,
+      raw::Action::Push(raw::Push {
+        values: vec![PushValue::Sint32(1)],
+      })
+    );
+    assert_eq!(decoded.trivia().trailing_bytes, vec![0x0a, 0x01, 0x02]);
+    let error = ParseError {
+      offset: 0,
+      code: 0x96,
+      field: "values",
+      kind: ParseErrorKind::UnknownPushValueType(0x0a),
+    };
+    assert_eq!(
+      decoded.diagnostics,
+      vec![Diagnostic::PartialPush {
+        error,
+        values: 1,
+        tail: vec![0x0a, 0x01, 0x02],
+      }]
+    );
+
+    // Truncated `Sint32` value: not recovered
+    let input = [0x96, 0x03, 0x00, 0x07, 0x01, 0x00];
+    let (_, decoded) = parse_decoded_action(&input[..], 0, &options).ok().unwrap();
+    assert!(matches!(decoded.action, raw::Action::Error(_)));
+    assert!(!decoded
+      .diagnostics
+      .iter()
+      .any(|diagnostic| matches!(diagnostic, Diagnostic::PartialPush { .. })));
+  }
+
+  #[test]
+  fn test_parse_action_detailed() {
+    let options = ParseOptions::default();
+    let input = [0x9a, 0x01, 0x00, 0xc0, 0x07];
+    let expected = ParseError {
+      offset: 0,
+      code: 0x9a,
+      field: "method",
+      kind: ParseErrorKind::UnknownGetUrl2Method(3),
+    };
+    assert_eq!(
+      parse_action_detailed(&input[..], &options),
+      Ok((&[0x07][..], Err(expected)))
+    );
+    assert_eq!(
+      parse_action_detailed(&input[4..], &options),
+      Ok((&[][..], Ok(raw::Action::Stop)))
+    );
+  }
+
+  #[test]
+  fn test_parse_action_error() {
+    {
+      let input = [0x96, 0x07, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x01];
+      let expected = ParseError {
+        offset: 0,
+        code: 0x96,
+        field: "values",
+        kind: ParseErrorKind::UnknownPushValueType(0x0a),
+      };
+      assert_eq!(
+        parse_action(&input[..]),
+        Ok((&[][..], raw::Action::Error(expected.into())))
+      );
+    }
+    {
+      let input = [0x83, 0x03, 0x00, 0x61, 0x00, 0x62];
+      let expected = ParseError {
+        offset: 0,
+        code: 0x83,
+        field: "target",
+        kind: ParseErrorKind::UnterminatedString,
+      };
+      assert_eq!(
+        parse_action(&input[..]),
+        Ok((&[][..], raw::Action::Error(expected.into())))
+      );
+    }
+    {
+      let input = [0x96, 0x03, 0x00, 0x00, 0xff, 0x00];
+      let expected = ParseError {
+        offset: 0,
+        code: 0x96,
+        field: "values",
+        kind: ParseErrorKind::InvalidString,
+      };
+      assert_eq!(
+        parse_action(&input[..]),
+        Ok((&[][..], raw::Action::Error(expected.into())))
+      );
+    }
+    {
+      let input = [0x88, 0x01, 0x00, 0x01];
+      let expected = ParseError {
+        offset: 0,
+        code: 0x88,
+        field: "pool.len",
+        kind: ParseErrorKind::UnexpectedEnd,
+      };
+      assert_eq!(
+        parse_action(&input[..]),
+        Ok((&[][..], raw::Action::Error(expected.into())))
+      );
+    }
+    {
+      let input = [0x9d, 0x01, 0x00, 0x00];
+      let expected = ParseError {
+        offset: 0,
+        code: 0x9d,
+        field: "offset",
+        kind: ParseErrorKind::UnexpectedEnd,
+      };
+      assert_eq!(
+        parse_action(&input[..]),
+        Ok((&[][..], raw::Action::Error(expected.into())))
+      );
+    }
+  }
 }
@@ -1,26 +1,63 @@
-use nom::number::complete::le_u64 as parse_le_u64;
+use crate::error::{BodyError, ParseErrorKind};
+use crate::options::ParseOptions;
+use nom::number::complete::{le_f32, le_i16, le_i32, le_u16, le_u64, le_u8};
 use nom::IResult as NomResult;
 
+type BodyResult<'a, T> = NomResult<&'a [u8], T, BodyError>;
+
 /// Parse a null-terminated sequence of bytes. The nul-byte is consumed but not included in the
-/// result.
-pub(crate) fn parse_c_string(input: &[u8]) -> NomResult<&[u8], String> {
+/// result. The bytes are decoded according to `options`.
+pub(crate) fn parse_c_string<'a>(input: &'a [u8], options: &ParseOptions) -> BodyResult<'a, String> {
   const NUL_BYTE: &[u8] = b"\x00";
 
-  let (input, raw) = nom::bytes::streaming::take_until(NUL_BYTE)(input)?;
+  let (input, raw) = match nom::bytes::streaming::take_until(NUL_BYTE)(input) {
+    Err(nom::Err::Incomplete(_)) => {
+      return Err(nom::Err::Error(BodyError::missing(
+        ParseErrorKind::UnterminatedString,
+        input.len(),
+        NUL_BYTE.len(),
+      )))
+    }
+    result => result?,
+  };
   let (input, _) = nom::bytes::streaming::take(NUL_BYTE.len())(input)?;
 
-  match std::str::from_utf8(raw) {
-    Ok(checked) => Ok((input, checked.to_string())),
-    Err(_) => Err(nom::Err::Error(nom::error::Error::new(
-      input,
-      nom::error::ErrorKind::Verify,
-    ))),
+  match options.decode_string(raw) {
+    Some(decoded) => Ok((input, decoded)),
+    None => Err(nom::Err::Error(BodyError::new(ParseErrorKind::InvalidString))),
   }
 }
 
-pub(crate) fn parse_le32_f64(input: &[u8]) -> NomResult<&[u8], f64> {
-  let (input, bits) = parse_le_u64(input)?;
-  let bits = (bits >> 32) | (bits << 32);
+/// Parses a fixed-size number with `parser`, reporting the number of missing bytes if the input
+/// is too short.
+fn parse_fixed<'a, O>(input: &'a [u8], size: usize, parser: fn(&'a [u8]) -> BodyResult<'a, O>) -> BodyResult<'a, O> {
+  parser(input)
+    .map_err(|e| e.map(|_| BodyError::missing(ParseErrorKind::UnexpectedEnd, input.len(), size - input.len())))
+}
+
+pub(crate) fn parse_u8(input: &[u8]) -> BodyResult<'_, u8> {
+  parse_fixed(input, 1, le_u8)
+}
+
+pub(crate) fn parse_le_u16(input: &[u8]) -> BodyResult<'_, u16> {
+  parse_fixed(input, 2, le_u16)
+}
+
+pub(crate) fn parse_le_i16(input: &[u8]) -> BodyResult<'_, i16> {
+  parse_fixed(input, 2, le_i16)
+}
+
+pub(crate) fn parse_le_i32(input: &[u8]) -> BodyResult<'_, i32> {
+  parse_fixed(input, 4, le_i32)
+}
+
+pub(crate) fn parse_le_f32(input: &[u8]) -> BodyResult<'_, f32> {
+  parse_fixed(input, 4, le_f32)
+}
+
+pub(crate) fn parse_le32_f64(input: &[u8]) -> BodyResult<'_, f64> {
+  let (input, bits) = parse_fixed(input, 8, le_u64)?;
+  let bits = bits.rotate_left(32);
   let bytes = bits.to_le_bytes();
   Ok((input, f64::from_le_bytes(bytes)))
 }
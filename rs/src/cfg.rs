@@ -1,8 +1,15 @@
-use crate::avm1::parse_action_header;
-use crate::parse_action;
+use crate::convert::{action_code, convert_action, successors_with, ActionSuccessors, FromRawActionError};
+use crate::diagnostic::{normalize_diagnostics, Diagnostic};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::labels::{parse_layer_label, rename_labels, LabelStyle};
+use crate::options::{OversizedBodyPolicy, ParseOptions};
+use crate::reader::{ActionParts, ActionReader};
+use crate::source_map::{BlockSpan, SourceMap};
 use avm1_types::cfg;
 use avm1_types::cfg::{Cfg, CfgBlock, CfgFlow, CfgLabel};
+use avm1_types::error::InvalidActionError;
 use avm1_types::raw;
+use core::cmp::Reverse;
 use core::convert::TryFrom;
 use core::iter::Iterator;
 use core::ops::Range;
@@ -13,75 +20,500 @@ type Avm1Index = usize;
 type Avm1Range = Range<usize>;
 
 pub fn parse_cfg(avm1: &[u8]) -> Cfg {
-  let mut idg = IdGen::new();
-  let parser = Avm1Parser::new(avm1);
-  let range: Avm1Range = 0..avm1.len();
-  let mut parse_cx = ParseContext::new(&mut idg, range);
-  parse_into_cfg(&parser, &mut parse_cx)
+  parse_cfg_with(avm1, &ParseOptions::default())
 }
 
-/// Block identifier generator
-#[derive(Clone, Debug, Eq, PartialEq)]
-struct IdGen(u64);
+pub fn parse_cfg_with(avm1: &[u8], options: &ParseOptions) -> Cfg {
+  parse_cfg_report(avm1, options).cfg
+}
 
-impl IdGen {
-  fn new() -> Self {
-    IdGen(0)
+/// Result of the CFG parser, with the diagnostics collected while building it
+#[derive(Clone, Debug, PartialEq)]
+pub struct CfgReport {
+  pub cfg: Cfg,
+  /// Byte ranges of the blocks and actions of `cfg`
+  pub source_map: SourceMap,
+  /// Diagnostics, sorted by offset
+  pub diagnostics: Vec<Diagnostic>,
+  /// Causes of the `Error` flows of `cfg`, by block label, when they are known
+  pub errors: BTreeMap<CfgLabel, ParseError>,
+}
+
+pub fn parse_cfg_report(avm1: &[u8], options: &ParseOptions) -> CfgReport {
+  let reader = ActionReader::with_options(avm1, *options);
+  build_report(&reader, options)
+}
+
+/// Builds a CFG from decoded actions, given as `(offset, len, action)` tuples.
+///
+/// The actions can come from an `ActionReader` or be edited in memory: offsets not covered by
+/// an action are reported with an error flow.
+pub fn build_cfg(actions: impl IntoIterator<Item = (usize, usize, raw::Action)>) -> Cfg {
+  build_cfg_report(actions, &ParseOptions::default()).cfg
+}
+
+pub fn build_cfg_report(
+  actions: impl IntoIterator<Item = (usize, usize, raw::Action)>,
+  options: &ParseOptions,
+) -> CfgReport {
+  let actions = LocatedActions::new(actions);
+  build_report(&actions, options)
+}
+
+/// CFG whose function bodies are not built yet
+///
+/// Lazy CFGs don't have a source map or diagnostics, and don't support all the `ParseOptions`:
+/// see `parse_cfg_lazy`.
+#[derive(Clone, Debug)]
+pub struct LazyCfg<'a> {
+  /// CFG where the body of each function definition is a placeholder: a single block labeled
+  /// like the entry block of the function, without actions and ending the code.
+  pub cfg: Cfg,
+  /// Functions defined in `cfg`, by the label of their entry block
+  pub functions: BTreeMap<CfgLabel, FunctionHandle<'a>>,
+}
+
+/// Function body whose CFG is built on demand
+///
+/// The labels of the built CFG match the labels produced by the eager parser.
+#[derive(Clone, Debug)]
+pub struct FunctionHandle<'a> {
+  reader: ActionReader<'a>,
+  function: LazyFunction,
+}
+
+impl<'a> FunctionHandle<'a> {
+  /// Byte range of the function body
+  pub fn range(&self) -> Avm1Range {
+    self.function.range.clone()
   }
 
-  fn next(&mut self) -> u64 {
-    let result: u64 = self.0;
-    self.0 += 1;
-    result
+  /// Label of the entry block of the function body
+  pub fn label(&self) -> CfgLabel {
+    layer_label(self.function.idg.0, self.function.range.start)
+  }
+
+  /// Builds the CFG of the function body, including the bodies of nested functions.
+  pub fn build(&self) -> Cfg {
+    self.build_with(false).cfg
+  }
+
+  /// Builds the CFG of the function body, leaving the bodies of nested functions unbuilt.
+  pub fn build_lazy(&self) -> LazyCfg<'a> {
+    self.build_with(true)
+  }
+
+  fn build_with(&self, lazy: bool) -> LazyCfg<'a> {
+    let mut state = BuildState::new(self.reader.options());
+    state.idg = self.function.idg.clone();
+    state.depth = self.function.depth;
+    state.lazy = lazy;
+    let mut parse_cx = ParseContext::new(&mut state, self.function.range.clone());
+    let cfg = parse_into_cfg(&self.reader, &mut parse_cx);
+    lazy_cfg(self.reader, cfg, state.functions)
+  }
+}
+
+/// Parses a CFG without building the function bodies.
+///
+/// The CFG of the function bodies is only built when requested through their `FunctionHandle`.
+/// The bodies are still traversed to reserve the layer ids they use, so the labels match the ones
+/// of the eager parser, but their actions don't use the `ParseLimits::max_actions` budget.
+///
+/// Unlike `parse_cfg_report`, the lazy parser doesn't return diagnostics, error causes or a source
+/// map, and ignores `ParseOptions::keep_unreachable` and `ParseOptions::labels`: the labels always
+/// use `LabelStyle::Layer`. When the action budget is exhausted, the lazy CFG may differ from the
+/// eager CFG.
+pub fn parse_cfg_lazy<'a>(avm1: &'a [u8], options: &ParseOptions) -> LazyCfg<'a> {
+  let reader = ActionReader::with_options(avm1, *options);
+  let mut state = BuildState::new(options);
+  state.lazy = true;
+  let mut parse_cx = ParseContext::new(&mut state, 0..avm1.len());
+  let cfg = parse_into_cfg(&reader, &mut parse_cx);
+  lazy_cfg(reader, cfg, state.functions)
+}
+
+fn lazy_cfg(reader: ActionReader<'_>, cfg: Cfg, functions: Vec<LazyFunction>) -> LazyCfg<'_> {
+  let functions = functions
+    .into_iter()
+    .map(|function| {
+      let handle = FunctionHandle { reader, function };
+      (handle.label(), handle)
+    })
+    .collect();
+  LazyCfg { cfg, functions }
+}
+
+fn build_report<S: ActionSource>(source: &S, options: &ParseOptions) -> CfgReport {
+  let mut state = BuildState::new(options);
+  let range: Avm1Range = 0..source.end();
+  let mut parse_cx = ParseContext::new(&mut state, range);
+  let mut cfg = parse_into_cfg(source, &mut parse_cx);
+  if options.keep_unreachable {
+    add_orphan_blocks(source, &mut state, &mut cfg);
+  }
+  let mut diagnostics = state.diagnostics;
+  normalize_diagnostics(&mut diagnostics);
+  state.source_map.detect_overlaps();
+  if options.labels != LabelStyle::Layer {
+    let mut style = options.labels;
+    let names = rename_labels(&mut cfg, &mut style);
+    state.source_map.rename(&names);
+    state.errors = state
+      .errors
+      .into_iter()
+      .map(|(label, error)| (names.get(&label).cloned().unwrap_or(label), error))
+      .collect();
+  }
+  CfgReport {
+    cfg,
+    source_map: state.source_map,
+    diagnostics,
+    errors: state.errors,
+  }
+}
+
+/// Appends the unreachable code to the CFG, as orphan blocks.
+///
+/// The uncovered ranges are split at the boundaries of the layers (function, `try` and `with`
+/// bodies), then each range is decoded linearly as a new layer. Its blocks are appended to the
+/// CFG of the innermost layer containing the range. The orphan blocks are marked as unreachable in
+/// the source map.
+///
+/// Targets outside the range resolve to the reachable blocks starting at the target offset in the
+/// enclosing layers, up to the enclosing function body. Other targets (e.g. inside a block) resolve
+/// to the end of the code.
+fn add_orphan_blocks<S: ActionSource>(source: &S, state: &mut BuildState, cfg: &mut Cfg) {
+  let reachable: Vec<CfgLabel> = state.source_map.blocks.keys().cloned().collect();
+  let mut layers: Vec<LayerSpan> = core::mem::take(&mut state.layers);
+  layers.sort_by_key(|layer| (layer.range.start, Reverse(layer.range.end)));
+  let mut boundaries: Vec<Avm1Index> = layers
+    .iter()
+    .flat_map(|layer| [layer.range.start, layer.range.end])
+    .collect();
+  boundaries.sort_unstable();
+  boundaries.dedup();
+
+  let mut orphans: HashMap<CfgLabel, Vec<CfgBlock>> = HashMap::new();
+  // Layers containing the current range, sorted by start offset
+  let mut enclosing: Vec<&LayerSpan> = Vec::new();
+  let mut next_layers = layers.iter().peekable();
+  state.linear = true;
+  state.outer_blocks = state
+    .source_map
+    .blocks
+    .keys()
+    .filter_map(|label| parse_layer_label(label).map(|key| (key, label.clone())))
+    .collect();
+  'orphans: for uncovered in state.source_map.uncovered(source.end()) {
+    let first: usize = boundaries.partition_point(|offset| *offset <= uncovered.start);
+    let ends = boundaries[first..]
+      .iter()
+      .copied()
+      .take_while(|offset| *offset < uncovered.end)
+      .chain(core::iter::once(uncovered.end));
+    let mut start: Avm1Index = uncovered.start;
+    for end in ends {
+      let range: Avm1Range = start..end;
+      start = end;
+      while let Some(layer) = next_layers.next_if(|layer| layer.range.start <= range.start) {
+        enclosing.push(layer);
+      }
+      // Layers ending before the range don't contain the next ranges either
+      while enclosing.last().map_or(false, |layer| layer.range.end < range.end) {
+        enclosing.pop();
+      }
+      // The root layer contains all the ranges
+      let layer: &LayerSpan = match enclosing.last() {
+        Some(layer) => layer,
+        None => continue,
+      };
+      if state.remaining_actions == Some(0) {
+        break 'orphans;
+      }
+      state.depth = layer.depth - 1;
+      let function: usize = enclosing.iter().rposition(|layer| layer.function).unwrap_or(0);
+      state.outer_layers = enclosing[function..].iter().map(|layer| layer.id).collect();
+      let mut parse_cx = ParseContext::new(state, range);
+      let orphan: Cfg = parse_into_cfg(source, &mut parse_cx);
+      orphans
+        .entry(layer_label(layer.id, layer.range.start))
+        .or_default()
+        .extend(orphan.blocks);
+    }
+  }
+  state.linear = false;
+  state.outer_blocks.clear();
+  state.outer_layers.clear();
+  state.depth = 0;
+
+  attach_orphans(cfg, &mut orphans);
+  for (label, block) in state.source_map.blocks.iter_mut() {
+    if reachable.binary_search(label).is_err() {
+      block.unreachable = true;
+    }
   }
 }
 
-fn try_add_offset(left: usize, right: i16) -> Option<usize> {
-  if right >= 0 {
-    let right_u16: u16 = u16::try_from(right).unwrap();
-    let right_usize = usize::from(right_u16);
-    left.checked_add(right_usize)
-  } else {
-    let right_u16: u16 = u16::try_from(-right).unwrap();
-    let right_usize = usize::from(right_u16);
-    left.checked_sub(right_usize)
+/// Appends the orphan blocks to the CFGs (including nested CFGs) whose entry block has the
+/// corresponding label.
+fn attach_orphans(cfg: &mut Cfg, orphans: &mut HashMap<CfgLabel, Vec<CfgBlock>>) {
+  if orphans.is_empty() {
+    return;
+  }
+  let blocks: Option<Vec<CfgBlock>> = orphans.remove(&cfg.blocks.first().label);
+  for block in cfg.blocks.iter_mut() {
+    for action in block.actions.iter_mut() {
+      match action {
+        cfg::Action::DefineFunction(action) => attach_orphans(&mut action.body, orphans),
+        cfg::Action::DefineFunction2(action) => attach_orphans(&mut action.body, orphans),
+        _ => {}
+      }
+    }
+    match &mut block.flow {
+      CfgFlow::Try(flow) => {
+        attach_orphans(&mut flow.r#try, orphans);
+        if let Some(catch) = flow.catch.as_mut() {
+          attach_orphans(&mut catch.body, orphans);
+        }
+        if let Some(finally) = flow.finally.as_mut() {
+          attach_orphans(finally, orphans);
+        }
+      }
+      CfgFlow::With(flow) => attach_orphans(&mut flow.body, orphans),
+      _ => {}
+    }
   }
+  cfg.blocks.extend(blocks.into_iter().flatten());
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-struct Avm1Parser<'a> {
-  bytes: &'a [u8],
+/// Actions read by the CFG parser
+trait ActionSource {
+  /// Reads the action at `offset`, see `ActionReader::read_parts_at`.
+  fn read_at(&self, offset: usize) -> ActionParts;
+
+  /// Returns the code of the action at `offset` without decoding it, `0x00` past the end of the
+  /// code.
+  fn code_at(&self, offset: usize) -> u8;
+
+  /// See `ActionReader::skip_partial_from`
+  fn skip_partial_from(&self, offset: usize, count: usize) -> (usize, usize);
+
+  /// End offset of the code
+  fn end(&self) -> usize;
 }
 
-impl<'a> Avm1Parser<'a> {
-  fn new(bytes: &'a [u8]) -> Self {
-    Avm1Parser { bytes }
+impl ActionSource for ActionReader<'_> {
+  fn read_at(&self, offset: usize) -> ActionParts {
+    self.read_parts_at(offset)
+  }
+
+  fn code_at(&self, offset: usize) -> u8 {
+    self.bytes().get(offset).copied().unwrap_or(0x00)
+  }
+
+  fn skip_partial_from(&self, offset: usize, count: usize) -> (usize, usize) {
+    ActionReader::skip_partial_from(self, offset, count)
   }
 
-  fn get(&self, offset: usize) -> (usize, raw::Action) {
-    if offset >= self.bytes.len() {
-      return (offset, raw::Action::End);
+  fn end(&self) -> usize {
+    self.bytes().len()
+  }
+}
+
+/// Pre-decoded actions, indexed by offset
+struct LocatedActions {
+  actions: BTreeMap<Avm1Index, (usize, raw::Action)>,
+  end: Avm1Index,
+}
+
+impl LocatedActions {
+  fn new(actions: impl IntoIterator<Item = (usize, usize, raw::Action)>) -> Self {
+    let mut located: BTreeMap<Avm1Index, (usize, raw::Action)> = BTreeMap::new();
+    let mut end: Avm1Index = 0;
+    for (offset, len, action) in actions {
+      end = end.max(offset + len);
+      located.insert(offset, (len, action));
     }
-    let input: &[u8] = &self.bytes[offset..];
-    match parse_action(input) {
-      Ok((next_input, action)) => (offset + (input.len() - next_input.len()), action),
-      Err(_) => (offset, raw::Action::Error(raw::Error { error: None })),
+    Self { actions: located, end }
+  }
+}
+
+impl ActionSource for LocatedActions {
+  fn read_at(&self, offset: usize) -> ActionParts {
+    match self.actions.get(&offset) {
+      Some((len, action)) => ActionParts::new(*len, action.clone()),
+      None if offset >= self.end => ActionParts::new(0, raw::Action::End),
+      None => {
+        let error = InvalidActionError {
+          message: format!("no action at offset {}", offset),
+        };
+        ActionParts::new(0, raw::Action::Error(raw::Error { error: Some(error) }))
+      }
     }
   }
 
-  fn skip(&self, offset: usize, action_count: usize) -> usize {
-    let mut input: &[u8] = &self.bytes[offset..];
-    for _ in 0..action_count {
-      let (next_input, header) = parse_action_header(input).unwrap();
-      input = &next_input[header.length..];
+  fn code_at(&self, offset: usize) -> u8 {
+    self
+      .actions
+      .get(&offset)
+      .and_then(|(_, action)| action_code(action))
+      .unwrap_or(0x00)
+  }
+
+  fn skip_partial_from(&self, mut offset: usize, count: usize) -> (usize, usize) {
+    for skipped in 0..count {
+      match self.actions.get(&offset) {
+        Some((len, _)) => offset += len,
+        None => return (offset, skipped),
+      }
     }
-    self.bytes.len() - input.len()
+    (offset, count)
+  }
+
+  fn end(&self) -> usize {
+    self.end
+  }
+}
+
+/// State shared by all the layers and nested functions of a CFG
+#[derive(Debug, Eq, PartialEq)]
+struct BuildState {
+  idg: IdGen,
+  /// Number of actions that may still be decoded, `None` if unlimited
+  remaining_actions: Option<usize>,
+  /// Number of CFGs being built (the current CFG and its ancestors)
+  depth: usize,
+  /// Maximum nesting depth, `None` if unlimited
+  max_depth: Option<usize>,
+  oversized_bodies: OversizedBodyPolicy,
+  diagnostics: Vec<Diagnostic>,
+  source_map: SourceMap,
+  /// Causes of the `Error` flows, by block label
+  errors: BTreeMap<CfgLabel, ParseError>,
+  /// Leave the function bodies unbuilt
+  lazy: bool,
+  /// Traverse a lazy function body only to reserve the ids of its layers: its actions don't use
+  /// the decoding budget and nothing is reported
+  counting: bool,
+  /// Decode the layers linearly: the actions following a non-linear flow start a new block even if
+  /// they are not reached, see `add_orphan_blocks`
+  linear: bool,
+  /// Byte ranges of the parsed layers
+  layers: Vec<LayerSpan>,
+  /// Blocks already built, by layer id and start offset, used to resolve the targets of orphan
+  /// code outside its layers
+  outer_blocks: HashMap<(u64, Avm1Index), CfgLabel>,
+  /// Ids of the layers enclosing the orphan code being decoded, innermost last
+  outer_layers: Vec<u64>,
+  /// Unbuilt function bodies
+  functions: Vec<LazyFunction>,
+}
+
+/// Byte range of a parsed layer
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct LayerSpan {
+  range: Avm1Range,
+  id: u64,
+  /// Depth of the layer CFG, see `BuildState::depth`
+  depth: usize,
+  /// The layer is the root code or a function body: the code it contains can't jump out of it
+  function: bool,
+}
+
+/// Unbuilt function body
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct LazyFunction {
+  range: Avm1Range,
+  /// State of the id generator at the start of the body: its next id is the id of the function
+  /// layer, followed by the ids of the nested layers
+  idg: IdGen,
+  /// Depth of the CFG defining the function
+  depth: usize,
+}
+
+impl LazyFunction {
+  /// Returns the CFG standing for the unbuilt body in the lazy CFG: a single block labeled like
+  /// the entry block of the body, without actions and ending the code.
+  fn placeholder(&self) -> Cfg {
+    let block = CfgBlock {
+      label: layer_label(self.idg.0, self.range.start),
+      actions: Vec::new(),
+      flow: CfgFlow::Simple(cfg::Simple { next: None }),
+    };
+    Cfg {
+      blocks: Vec1::new(block),
+    }
+  }
+}
+
+impl BuildState {
+  fn new(options: &ParseOptions) -> Self {
+    Self {
+      idg: IdGen::new(),
+      remaining_actions: options.limits.max_actions,
+      depth: 0,
+      max_depth: options.limits.max_depth,
+      oversized_bodies: options.oversized_bodies,
+      diagnostics: Vec::new(),
+      source_map: SourceMap::new(),
+      errors: BTreeMap::new(),
+      lazy: false,
+      counting: false,
+      linear: false,
+      layers: Vec::new(),
+      outer_blocks: HashMap::new(),
+      outer_layers: Vec::new(),
+      functions: Vec::new(),
+    }
+  }
+
+  fn report(&mut self, diagnostic: Diagnostic) {
+    if !self.counting {
+      self.diagnostics.push(diagnostic);
+    }
+  }
+
+  /// Consumes one action from the decoding budget, returns `false` if it is exhausted.
+  fn take_action(&mut self) -> bool {
+    if self.counting {
+      return true;
+    }
+    match self.remaining_actions.as_mut() {
+      None => true,
+      Some(0) => false,
+      Some(remaining) => {
+        *remaining -= 1;
+        true
+      }
+    }
+  }
+
+  /// Returns `true` if the current CFG may contain nested CFGs.
+  fn can_nest(&self) -> bool {
+    self.max_depth.map_or(true, |max_depth| self.depth <= max_depth)
+  }
+}
+
+/// Block identifier generator
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct IdGen(u64);
+
+impl IdGen {
+  fn new() -> Self {
+    IdGen(0)
+  }
+
+  fn next(&mut self) -> u64 {
+    let result: u64 = self.0;
+    self.0 += 1;
+    result
   }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 struct ParseContext<'a> {
-  idg: &'a mut IdGen,
+  state: &'a mut BuildState,
   layers: Vec<LayerContext>,
 }
 
@@ -118,8 +550,8 @@ struct LayerContext {
 }
 
 impl<'a> ParseContext<'a> {
-  fn new(idg: &'a mut IdGen, range: Avm1Range) -> Self {
-    let id: u64 = idg.next();
+  fn new(state: &'a mut BuildState, range: Avm1Range) -> Self {
+    let id: u64 = state.idg.next();
     let mut layer = LayerContext {
       id,
       range,
@@ -129,13 +561,13 @@ impl<'a> ParseContext<'a> {
     layer.actions.insert(layer.range.start, Reachability::Jump);
     layer.new_actions.push(layer.range.start);
     Self {
-      idg,
+      state,
       layers: vec![layer],
     }
   }
 
   fn push_layer(&mut self, range: Avm1Range) {
-    let id: u64 = self.idg.next();
+    let id: u64 = self.state.idg.next();
     let mut layer = LayerContext {
       id,
       range,
@@ -181,11 +613,17 @@ impl<'a> ParseContext<'a> {
             new_actions.push(index);
             Reachability::Jump
           });
-        return Some(CfgLabel(format!("l{}_{}", layer.id, index)));
+        return Some(layer_label(layer.id, index));
       };
       is_top = false;
     }
-    None
+    let outer_blocks = &self.state.outer_blocks;
+    self
+      .state
+      .outer_layers
+      .iter()
+      .rev()
+      .find_map(|id| outer_blocks.get(&(*id, index)).cloned())
   }
 
   fn pop_action(&mut self) -> Option<Avm1Index> {
@@ -208,7 +646,7 @@ impl<'a> ParseContext<'a> {
   fn get_target_label(&self, target: Avm1Index) -> Option<CfgLabel> {
     for layer in self.layers.iter().rev() {
       if layer.range.contains(&target) {
-        return Some(CfgLabel(format!("l{}_{}", layer.id, target)));
+        return Some(layer_label(layer.id, target));
       }
     }
     None
@@ -221,545 +659,439 @@ enum Parsed {
   Flow(CfgFlow),
 }
 
-fn parse_into_cfg(parser: &Avm1Parser, traversal: &mut ParseContext) -> Cfg {
-  let mut parsed: HashMap<usize, Parsed> = HashMap::new();
-
-  while let Some(cur_offset) = traversal.pop_action() {
-    if !traversal.top_layer().range.contains(&cur_offset) {
-      let jump = cfg::Simple {
-        next: traversal.jump(cur_offset),
-      };
-      parsed.insert(cur_offset, Parsed::Flow(CfgFlow::Simple(jump)));
-      continue;
-    }
+fn parse_into_cfg<S: ActionSource>(source: &S, traversal: &mut ParseContext) -> Cfg {
+  traversal.state.depth += 1;
+  if !traversal.state.counting {
+    let layer = LayerSpan {
+      range: traversal.top_layer().range.clone(),
+      id: traversal.top_layer().id,
+      depth: traversal.state.depth,
+      function: traversal.layers.len() == 1,
+    };
+    traversal.state.layers.push(layer);
+  }
+  let cfg = parse_layer_into_cfg(source, traversal);
+  traversal.state.depth -= 1;
+  cfg
+}
 
-    let (end_offset, raw) = parser.get(cur_offset);
+fn parse_layer_into_cfg<S: ActionSource>(source: &S, traversal: &mut ParseContext) -> Cfg {
+  let mut parsed: HashMap<usize, Parsed> = HashMap::new();
+  // Encoded length of the decoded actions
+  let mut lengths: HashMap<usize, usize> = HashMap::new();
+  // Causes of the error flows
+  let mut errors: HashMap<usize, ParseError> = HashMap::new();
+  // End offset of the decoded actions, including their code blocks
+  let mut extents: HashMap<usize, usize> = HashMap::new();
+  // Next offset of the linear decoding, see `BuildState::linear`
+  let mut cursor: Avm1Index = traversal.top_layer().range.start;
 
-    let cur_parsed: Parsed = match raw {
-      raw::Action::Add => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Add)
-      }
-      raw::Action::Add2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Add2)
-      }
-      raw::Action::And => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::And)
-      }
-      raw::Action::AsciiToChar => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::AsciiToChar)
-      }
-      raw::Action::BitAnd => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitAnd)
-      }
-      raw::Action::BitOr => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitOr)
-      }
-      raw::Action::BitLShift => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitLShift)
-      }
-      raw::Action::BitRShift => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitRShift)
-      }
-      raw::Action::BitURShift => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitURShift)
-      }
-      raw::Action::BitXor => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::BitXor)
-      }
-      raw::Action::Call => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Call)
-      }
-      raw::Action::CallFunction => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::CallFunction)
-      }
-      raw::Action::CallMethod => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::CallMethod)
-      }
-      raw::Action::CharToAscii => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::CharToAscii)
-      }
-      raw::Action::CastOp => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::CastOp)
-      }
-      raw::Action::CloneSprite => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::CloneSprite)
-      }
-      raw::Action::ConstantPool(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ConstantPool(action))
-      }
-      raw::Action::Decrement => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Decrement)
-      }
-      raw::Action::DefineFunction(action) => {
-        let fn_range: Avm1Range = end_offset..(end_offset + usize::from(action.body_size));
-        let mut fn_child_traversal = ParseContext::new(traversal.idg, fn_range.clone());
-        let cfg: Cfg = parse_into_cfg(parser, &mut fn_child_traversal);
-        traversal.linear(fn_range.end);
-        Parsed::Action(
-          fn_range.end,
-          cfg::Action::DefineFunction(Box::new(cfg::DefineFunction {
-            name: action.name,
-            parameters: action.parameters,
-            body: cfg,
-          })),
-        )
-      }
-      raw::Action::DefineFunction2(action) => {
-        let fn_range: Avm1Range = end_offset..(end_offset + usize::from(action.body_size));
-        let mut fn_child_traversal = ParseContext::new(traversal.idg, fn_range.clone());
-        let cfg: Cfg = parse_into_cfg(parser, &mut fn_child_traversal);
-        traversal.linear(fn_range.end);
-        Parsed::Action(
-          fn_range.end,
-          cfg::Action::DefineFunction2(Box::new(cfg::DefineFunction2 {
-            name: action.name,
-            register_count: action.register_count,
-            flags: action.flags,
-            parameters: action.parameters,
-            body: cfg,
-          })),
-        )
-      }
-      raw::Action::DefineLocal => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::DefineLocal)
-      }
-      raw::Action::DefineLocal2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::DefineLocal2)
-      }
-      raw::Action::Delete => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Delete)
-      }
-      raw::Action::Delete2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Delete2)
-      }
-      raw::Action::Divide => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Divide)
-      }
-      raw::Action::End => Parsed::Flow(CfgFlow::Simple(cfg::Simple { next: None })),
-      raw::Action::EndDrag => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::EndDrag)
-      }
-      raw::Action::Enumerate => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Enumerate)
-      }
-      raw::Action::Enumerate2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Enumerate2)
-      }
-      raw::Action::Equals => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Equals)
-      }
-      raw::Action::Equals2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Equals2)
-      }
-      raw::Action::Error(action) => Parsed::Flow(CfgFlow::Error(cfg::Error { error: action.error })),
-      raw::Action::Extends => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Extends)
-      }
-      raw::Action::FsCommand2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::FsCommand2)
-      }
-      raw::Action::GetMember => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetMember)
-      }
-      raw::Action::GetProperty => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetProperty)
-      }
-      raw::Action::GetTime => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetTime)
-      }
-      raw::Action::GetUrl(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetUrl(action))
-      }
-      raw::Action::GetUrl2(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetUrl2(action))
-      }
-      raw::Action::GetVariable => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GetVariable)
-      }
-      raw::Action::GotoFrame(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GotoFrame(action))
-      }
-      raw::Action::GotoFrame2(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GotoFrame2(action))
-      }
-      raw::Action::GotoLabel(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::GotoLabel(action))
-      }
-      raw::Action::Greater => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Greater)
-      }
-      raw::Action::If(action) => {
-        let true_target = if let Some(jump_offset) = try_add_offset(end_offset, action.offset) {
-          traversal.jump(jump_offset)
-        } else {
-          None
+  loop {
+    while let Some(cur_offset) = traversal.pop_action() {
+      if !traversal.top_layer().range.contains(&cur_offset) {
+        let jump = cfg::Simple {
+          next: traversal.jump(cur_offset),
         };
-        let false_target = traversal.jump(end_offset);
-        Parsed::Flow(CfgFlow::If(cfg::If {
-          true_target,
-          false_target,
-        }))
-      }
-      raw::Action::ImplementsOp => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ImplementsOp)
-      }
-      raw::Action::Increment => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Increment)
-      }
-      raw::Action::InitArray => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::InitArray)
-      }
-      raw::Action::InitObject => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::InitObject)
+        parsed.insert(cur_offset, Parsed::Flow(CfgFlow::Simple(jump)));
+        continue;
       }
-      raw::Action::InstanceOf => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::InstanceOf)
-      }
-      raw::Action::Jump(action) => {
-        let next = if let Some(jump_offset) = try_add_offset(end_offset, action.offset) {
-          traversal.jump(jump_offset)
-        } else {
-          None
+
+      if !traversal.state.take_action() {
+        let error = ParseError {
+          offset: cur_offset,
+          code: source.code_at(cur_offset),
+          field: "action",
+          kind: ParseErrorKind::ActionLimitExceeded,
         };
-        Parsed::Flow(CfgFlow::Simple(cfg::Simple { next }))
-      }
-      raw::Action::Less => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Less)
-      }
-      raw::Action::Less2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Less2)
-      }
-      raw::Action::MbAsciiToChar => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::MbAsciiToChar)
-      }
-      raw::Action::MbCharToAscii => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::MbCharToAscii)
-      }
-      raw::Action::MbStringExtract => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::MbStringExtract)
-      }
-      raw::Action::MbStringLength => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::MbStringLength)
-      }
-      raw::Action::Modulo => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Modulo)
-      }
-      raw::Action::Multiply => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Multiply)
-      }
-      raw::Action::NewMethod => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::NewMethod)
-      }
-      raw::Action::NewObject => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::NewObject)
-      }
-      raw::Action::NextFrame => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::NextFrame)
-      }
-      raw::Action::Not => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Not)
-      }
-      raw::Action::Or => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Or)
-      }
-      raw::Action::Play => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Play)
-      }
-      raw::Action::Pop => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Pop)
-      }
-      raw::Action::PrevFrame => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::PrevFrame)
-      }
-      raw::Action::Push(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Push(action))
-      }
-      raw::Action::PushDuplicate => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::PushDuplicate)
-      }
-      raw::Action::RandomNumber => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::RandomNumber)
-      }
-      raw::Action::RemoveSprite => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::RemoveSprite)
-      }
-      raw::Action::Return => Parsed::Flow(CfgFlow::Return),
-      raw::Action::SetMember => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::SetMember)
-      }
-      raw::Action::SetProperty => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::SetProperty)
-      }
-      raw::Action::SetTarget(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::SetTarget(action))
-      }
-      raw::Action::SetTarget2 => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::SetTarget2)
-      }
-      raw::Action::SetVariable => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::SetVariable)
-      }
-      raw::Action::StackSwap => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StackSwap)
-      }
-      raw::Action::StartDrag => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StartDrag)
-      }
-      raw::Action::Stop => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Stop)
-      }
-      raw::Action::StopSounds => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StopSounds)
-      }
-      raw::Action::StoreRegister(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StoreRegister(action))
-      }
-      raw::Action::StrictEquals => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StrictEquals)
-      }
-      raw::Action::StrictMode(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StrictMode(action))
-      }
-      raw::Action::StringAdd => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringAdd)
-      }
-      raw::Action::StringEquals => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringEquals)
-      }
-      raw::Action::StringExtract => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringExtract)
-      }
-      raw::Action::StringGreater => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringGreater)
-      }
-      raw::Action::StringLength => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringLength)
+        parsed.insert(cur_offset, error_flow(&mut errors, error));
+        continue;
       }
-      raw::Action::StringLess => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::StringLess)
-      }
-      raw::Action::Subtract => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Subtract)
-      }
-      raw::Action::TargetPath => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::TargetPath)
-      }
-      raw::Action::Throw => Parsed::Flow(CfgFlow::Throw),
-      raw::Action::ToInteger => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ToInteger)
-      }
-      raw::Action::ToNumber => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ToNumber)
-      }
-      raw::Action::ToString => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ToString)
+
+      let ActionParts {
+        len,
+        action: mut raw,
+        error,
+        diagnostics,
+        ..
+      } = source.read_at(cur_offset);
+      for diagnostic in diagnostics {
+        traversal.state.report(diagnostic);
+      }
+      lengths.insert(cur_offset, len);
+      if let Some(error) = error {
+        errors.insert(cur_offset, error);
       }
-      raw::Action::ToggleQuality => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::ToggleQuality)
+
+      if has_nested_cfg(&raw) && !traversal.state.can_nest() {
+        let error = ParseError {
+          offset: cur_offset,
+          code: action_code(&raw).expect("actions with code blocks to have a code"),
+          field: "body",
+          kind: ParseErrorKind::NestingLimitExceeded,
+        };
+        parsed.insert(cur_offset, error_flow(&mut errors, error));
+        continue;
       }
-      raw::Action::Trace => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Trace)
+      let end_offset: Avm1Index = cur_offset + len;
+
+      if let Some(declared) = nested_cfg_size(&raw) {
+        let available: usize = traversal
+          .top_layer()
+          .range
+          .end
+          .min(source.end())
+          .saturating_sub(end_offset);
+        if declared > available {
+          let code: u8 = action_code(&raw).expect("actions with code blocks to have a code");
+          traversal.state.report(Diagnostic::OversizedBody {
+            offset: cur_offset,
+            code,
+            declared,
+            available,
+          });
+          match traversal.state.oversized_bodies {
+            OversizedBodyPolicy::Keep => {}
+            OversizedBodyPolicy::Clamp => clamp_nested_cfg_sizes(&mut raw, available),
+            OversizedBodyPolicy::Error => {
+              let error = ParseError {
+                offset: cur_offset,
+                code,
+                field: "body",
+                kind: ParseErrorKind::OversizedBody { declared, available },
+              };
+              parsed.insert(cur_offset, error_flow(&mut errors, error));
+              continue;
+            }
+          }
+        }
       }
-      raw::Action::Try(action) => {
-        let try_start: Avm1Index = end_offset;
-        let catch_start: Avm1Index = try_start + usize::from(action.r#try);
-        let finally_start: Avm1Index = catch_start + action.catch.as_ref().map_or(0, |c| usize::from(c.size));
-
-        let finally: Option<Cfg> = if let Some(finally_size) = action.finally {
-          traversal.push_layer(finally_start..(finally_start + usize::from(finally_size)));
-          Some(parse_into_cfg(parser, traversal))
-        } else {
-          None
-        };
 
-        let r#try = {
-          traversal.push_layer(try_start..(try_start + usize::from(action.r#try)));
-          let r#try: Cfg = parse_into_cfg(parser, traversal);
-          traversal.pop_layer();
-          r#try
-        };
+      // Number of actions skipped by `WaitForFrame`, when reaching the end of the code
+      let mut skipped: usize = 0;
+      let ActionSuccessors { successors, bodies, .. } = successors_with(cur_offset, len, &raw, |offset, count| {
+        let (target, count_skipped) = source.skip_partial_from(offset, count);
+        skipped = count_skipped;
+        Some(target).filter(|_| count_skipped == count)
+      });
+      // Successors of linear actions are always resolved
+      let next = || successors[0].expect("linear actions to have a next action");
+      extents.insert(cur_offset, bodies.last().map_or(end_offset, |body| body.end));
+      let mut bodies = bodies.into_iter();
 
-        let catch = action.catch.map(|raw_catch| {
-          traversal.push_layer(catch_start..(catch_start + usize::from(raw_catch.size)));
-          let body: Cfg = parse_into_cfg(parser, traversal);
-          traversal.pop_layer();
-          cfg::CatchBlock {
-            target: raw_catch.target,
-            body,
+      let cur_parsed: Parsed = match convert_action(raw) {
+        Ok(action) => {
+          traversal.linear(next());
+          Parsed::Action(next(), action)
+        }
+        Err(FromRawActionError::DefineFunction(action)) => {
+          let fn_range: Avm1Range = bodies.next().expect("function definitions to have a body");
+          let cfg: Cfg = parse_function_body(source, traversal, fn_range);
+          traversal.linear(next());
+          Parsed::Action(
+            next(),
+            cfg::Action::DefineFunction(Box::new(cfg::DefineFunction {
+              name: action.name,
+              parameters: action.parameters,
+              body: cfg,
+            })),
+          )
+        }
+        Err(FromRawActionError::DefineFunction2(action)) => {
+          let fn_range: Avm1Range = bodies.next().expect("function definitions to have a body");
+          let cfg: Cfg = parse_function_body(source, traversal, fn_range);
+          traversal.linear(next());
+          Parsed::Action(
+            next(),
+            cfg::Action::DefineFunction2(Box::new(cfg::DefineFunction2 {
+              name: action.name,
+              register_count: action.register_count,
+              flags: action.flags,
+              parameters: action.parameters,
+              body: cfg,
+            })),
+          )
+        }
+        Err(FromRawActionError::End) => Parsed::Flow(CfgFlow::Simple(cfg::Simple { next: None })),
+        Err(FromRawActionError::Error(action)) => Parsed::Flow(CfgFlow::Error(cfg::Error { error: action.error })),
+        Err(FromRawActionError::If(_)) => {
+          let true_target = successors[0].and_then(|target| traversal.jump(target));
+          let false_target = successors[1].and_then(|target| traversal.jump(target));
+          Parsed::Flow(CfgFlow::If(cfg::If {
+            true_target,
+            false_target,
+          }))
+        }
+        Err(FromRawActionError::Jump(_)) => {
+          let next = successors[0].and_then(|target| traversal.jump(target));
+          Parsed::Flow(CfgFlow::Simple(cfg::Simple { next }))
+        }
+        Err(FromRawActionError::Return) => Parsed::Flow(CfgFlow::Return),
+        Err(FromRawActionError::Throw) => Parsed::Flow(CfgFlow::Throw),
+        Err(FromRawActionError::Try(action)) => {
+          let try_range: Avm1Range = bodies.next().expect("`try` actions to have a `try` body");
+          let catch_range: Option<Avm1Range> = action.catch.as_ref().and_then(|_| bodies.next());
+          let finally_range: Option<Avm1Range> = action.finally.and_then(|_| bodies.next());
+
+          let finally: Option<Cfg> = finally_range.map(|range| {
+            traversal.push_layer(range);
+            parse_into_cfg(source, traversal)
+          });
+
+          let r#try = {
+            traversal.push_layer(try_range);
+            let r#try: Cfg = parse_into_cfg(source, traversal);
+            traversal.pop_layer();
+            r#try
+          };
+
+          let catch = action.catch.zip(catch_range).map(|(raw_catch, range)| {
+            traversal.push_layer(range);
+            let body: Cfg = parse_into_cfg(source, traversal);
+            traversal.pop_layer();
+            cfg::CatchBlock {
+              target: raw_catch.target,
+              body,
+            }
+          });
+
+          if finally.is_some() {
+            traversal.pop_layer();
           }
-        });
 
-        if finally.is_some() {
+          Parsed::Flow(CfgFlow::Try(Box::new(cfg::Try { r#try, catch, finally })))
+        }
+        Err(FromRawActionError::WaitForFrame(action)) => {
+          let loading_target = skip_target(
+            traversal,
+            cur_offset,
+            0x8a,
+            successors[0],
+            usize::from(action.skip),
+            skipped,
+          );
+          let ready_target = successors[1].and_then(|target| traversal.jump(target));
+          let wff = cfg::WaitForFrame {
+            frame: action.frame,
+            loading_target,
+            ready_target,
+          };
+          Parsed::Flow(CfgFlow::WaitForFrame(wff))
+        }
+        Err(FromRawActionError::WaitForFrame2(action)) => {
+          let loading_target = skip_target(
+            traversal,
+            cur_offset,
+            0x8d,
+            successors[0],
+            usize::from(action.skip),
+            skipped,
+          );
+          let ready_target = successors[1].and_then(|target| traversal.jump(target));
+          let wff = cfg::WaitForFrame2 {
+            ready_target,
+            loading_target,
+          };
+          Parsed::Flow(CfgFlow::WaitForFrame2(wff))
+        }
+        Err(FromRawActionError::With(_)) => {
+          traversal.push_layer(bodies.next().expect("`with` actions to have a body"));
+          let body: Cfg = parse_into_cfg(source, traversal);
           traversal.pop_layer();
+          Parsed::Flow(CfgFlow::With(cfg::With { body }))
         }
+      };
 
-        Parsed::Flow(CfgFlow::Try(Box::new(cfg::Try { r#try, catch, finally })))
-      }
-      raw::Action::TypeOf => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::TypeOf)
+      {
+        let old: Option<Parsed> = parsed.insert(cur_offset, cur_parsed);
+        debug_assert!(old.is_none());
       }
-      raw::Action::Raw(action) => {
-        traversal.linear(end_offset);
-        Parsed::Action(end_offset, cfg::Action::Raw(action))
-      }
-      raw::Action::WaitForFrame(action) => {
-        let loading_offset = parser.skip(end_offset, usize::from(action.skip));
-        let loading_target = traversal.jump(loading_offset);
-        let ready_target = traversal.jump(end_offset);
-        let wff = cfg::WaitForFrame {
-          frame: action.frame,
-          loading_target,
-          ready_target,
-        };
-        Parsed::Flow(CfgFlow::WaitForFrame(wff))
-      }
-      raw::Action::WaitForFrame2(action) => {
-        let loading_offset = parser.skip(end_offset, usize::from(action.skip));
-        let loading_target = traversal.jump(loading_offset);
-        let ready_target = traversal.jump(end_offset);
-        let wff = cfg::WaitForFrame2 {
-          ready_target,
-          loading_target,
-        };
-        Parsed::Flow(CfgFlow::WaitForFrame2(wff))
-      }
-      raw::Action::With(action) => {
-        let range: Avm1Range = end_offset..(end_offset + usize::from(action.size));
-        traversal.push_layer(range);
-        let body: Cfg = parse_into_cfg(parser, traversal);
-        traversal.pop_layer();
-        Parsed::Flow(CfgFlow::With(cfg::With { body }))
-      }
-    };
+    }
 
-    {
-      let old: Option<Parsed> = parsed.insert(cur_offset, cur_parsed);
-      debug_assert!(old.is_none());
+    if !traversal.state.linear {
+      break;
+    }
+    // Skip the decoded actions, the decoding stops at actions without a known end (limit errors)
+    let end: Avm1Index = traversal.top_layer().range.end;
+    while cursor < end && parsed.contains_key(&cursor) {
+      cursor = match extents.get(&cursor) {
+        Some(extent) if *extent > cursor => *extent,
+        _ => end,
+      };
+    }
+    if cursor >= end {
+      break;
     }
+    traversal.jump(cursor);
   }
 
   let mut blocks: Vec<CfgBlock> = Vec::new();
+  let mut block_spans: Vec<(CfgLabel, BlockSpan)> = Vec::new();
+  let mut block_errors: Vec<(CfgLabel, ParseError)> = Vec::new();
 
   for start_index in traversal.iter_labels() {
-    let label: CfgLabel = CfgLabel(format!("l{}_{}", traversal.top_layer().id, start_index));
+    let label: CfgLabel = layer_label(traversal.top_layer().id, start_index);
     let mut builder: CfgBlockBuilder = CfgBlockBuilder::new(label);
+    let mut action_spans: Vec<Avm1Range> = Vec::new();
     let mut index: Avm1Index = start_index;
-    let block: CfgBlock = loop {
+    let (block, flow_span): (CfgBlock, Option<Avm1Range>) = loop {
       let action = parsed
         .remove(&index)
         .expect("`parsed` to have actions found during traversal");
+      let span: Option<Avm1Range> = lengths.get(&index).map(|len| index..(index + len));
       match action {
         Parsed::Action(next, action) => {
           builder.action(action);
+          action_spans.push(span.expect("decoded actions to have a length"));
           index = next
         }
-        Parsed::Flow(flow) => break builder.flow(flow),
+        Parsed::Flow(flow) => {
+          if let Some(error) = errors.remove(&index) {
+            block_errors.push((builder.label.clone(), error));
+          }
+          break (builder.flow(flow), span);
+        }
       };
       if traversal.top_layer().actions.get(&index) == Some(&Reachability::Jump) {
         let jump = cfg::Simple {
           next: traversal.get_target_label(index),
         };
-        break builder.flow(CfgFlow::Simple(jump));
+        break (builder.flow(CfgFlow::Simple(jump)), None);
       }
     };
+    let end: Avm1Index = flow_span.as_ref().map_or(index, |flow| flow.end);
+    let block_span = BlockSpan {
+      range: start_index..end,
+      actions: action_spans,
+      flow: flow_span,
+      overlapping: false,
+      unreachable: false,
+    };
+    block_spans.push((block.label.clone(), block_span));
     blocks.push(block);
   }
+  if !traversal.state.counting {
+    traversal.state.source_map.blocks.extend(block_spans);
+    traversal.state.errors.extend(block_errors);
+  }
 
   let blocks: Vec1<CfgBlock> = Vec1::try_from_vec(blocks).unwrap();
   Cfg { blocks }
 }
 
+/// Returns the error flow replacing the action at `error.offset`, and records its cause.
+fn error_flow(errors: &mut HashMap<usize, ParseError>, error: ParseError) -> Parsed {
+  errors.insert(error.offset, error);
+  Parsed::Flow(CfgFlow::Error(cfg::Error {
+    error: Some(error.into()),
+  }))
+}
+
+/// Parses the body of a function defined in the current CFG.
+///
+/// In lazy mode, the body is only traversed to reserve the ids of its layers, and a placeholder is
+/// returned.
+fn parse_function_body<S: ActionSource>(source: &S, traversal: &mut ParseContext, fn_range: Avm1Range) -> Cfg {
+  let idg: IdGen = traversal.state.idg.clone();
+  let mut fn_child_traversal = ParseContext::new(traversal.state, fn_range.clone());
+  if !fn_child_traversal.state.lazy || fn_child_traversal.state.counting {
+    return parse_into_cfg(source, &mut fn_child_traversal);
+  }
+
+  let function = LazyFunction {
+    range: fn_range,
+    idg,
+    depth: fn_child_traversal.state.depth,
+  };
+  // Reserve the ids of the nested layers, in the same order as the eager parser
+  fn_child_traversal.state.counting = true;
+  parse_into_cfg(source, &mut fn_child_traversal);
+  fn_child_traversal.state.counting = false;
+  let placeholder: Cfg = function.placeholder();
+  fn_child_traversal.state.functions.push(function);
+  placeholder
+}
+
+/// Returns the label of the block starting at `offset` in the layer `id`.
+///
+/// See `LabelStyle::Layer`.
+fn layer_label(id: u64, offset: Avm1Index) -> CfgLabel {
+  CfgLabel(format!("l{}_{}", id, offset))
+}
+
+/// Returns the loading target label of the `WaitForFrame` action at `offset`, `target` being the
+/// offset reached after skipping its `skip` actions.
+///
+/// If the input ends after `skipped` actions, `target` is `None` and the loading target is the end
+/// of the code.
+fn skip_target(
+  traversal: &mut ParseContext,
+  offset: Avm1Index,
+  code: u8,
+  target: Option<Avm1Index>,
+  skip: usize,
+  skipped: usize,
+) -> Option<CfgLabel> {
+  match target {
+    Some(target) => traversal.jump(target),
+    None => {
+      traversal.state.report(Diagnostic::SkipOutOfBounds {
+        offset,
+        code,
+        skip,
+        skipped,
+      });
+      None
+    }
+  }
+}
+
+/// Returns the total size of the code blocks held by the action, if any.
+fn nested_cfg_size(action: &raw::Action) -> Option<usize> {
+  match action {
+    raw::Action::DefineFunction(action) => Some(usize::from(action.body_size)),
+    raw::Action::DefineFunction2(action) => Some(usize::from(action.body_size)),
+    raw::Action::Try(action) => {
+      let catch_size: u16 = action.catch.as_ref().map_or(0, |c| c.size);
+      let sizes = [action.r#try, catch_size, action.finally.unwrap_or(0)];
+      Some(sizes.iter().map(|size| usize::from(*size)).sum())
+    }
+    raw::Action::With(action) => Some(usize::from(action.size)),
+    _ => None,
+  }
+}
+
+/// Shrinks the code blocks held by the action so they fit in `available` bytes.
+fn clamp_nested_cfg_sizes(action: &mut raw::Action, available: usize) {
+  let mut available: u16 = u16::try_from(available).unwrap_or(u16::MAX);
+  let mut clamp = |size: &mut u16| {
+    *size = (*size).min(available);
+    available -= *size;
+  };
+  match action {
+    raw::Action::DefineFunction(action) => clamp(&mut action.body_size),
+    raw::Action::DefineFunction2(action) => clamp(&mut action.body_size),
+    raw::Action::Try(action) => {
+      clamp(&mut action.r#try);
+      if let Some(catch) = action.catch.as_mut() {
+        clamp(&mut catch.size);
+      }
+      if let Some(finally) = action.finally.as_mut() {
+        clamp(finally);
+      }
+    }
+    raw::Action::With(action) => clamp(&mut action.size),
+    _ => {}
+  }
+}
+
+/// Returns `true` if the action holds code blocks parsed as nested CFGs.
+fn has_nested_cfg(action: &raw::Action) -> bool {
+  matches!(
+    action,
+    raw::Action::DefineFunction(_) | raw::Action::DefineFunction2(_) | raw::Action::Try(_) | raw::Action::With(_)
+  )
+}
+
 struct CfgBlockBuilder {
   label: CfgLabel,
   actions: Vec<cfg::Action>,
@@ -786,3 +1118,430 @@ impl CfgBlockBuilder {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::options::ParseLimits;
+  use crate::source_map::{ActionSpan, Overlap};
+
+  #[test]
+  fn test_max_actions() {
+    let input = [0x06, 0x07, 0x00];
+    let options = ParseOptions {
+      limits: ParseLimits {
+        max_actions: Some(2),
+        ..ParseLimits::default()
+      },
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &options);
+    let error = ParseError {
+      offset: 2,
+      code: 0x00,
+      field: "action",
+      kind: ParseErrorKind::ActionLimitExceeded,
+    };
+    let expected = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label: CfgLabel(String::from("l0_0")),
+        actions: vec![cfg::Action::Play, cfg::Action::Stop],
+        flow: CfgFlow::Error(cfg::Error {
+          error: Some(error.into()),
+        }),
+      }),
+    };
+    assert_eq!(report.cfg, expected);
+    assert_eq!(report.errors, BTreeMap::from([(CfgLabel(String::from("l0_0")), error)]));
+  }
+
+  #[test]
+  fn test_parse_cfg_report() {
+    // Push [1, <unknown value type 10>]; Stop
+    let input = [0x96, 0x06, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x07, 0x00];
+    let options = ParseOptions {
+      recover_push: true,
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &options);
+    let expected = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label: CfgLabel(String::from("l0_0")),
+        actions: vec![
+          cfg::Action::Push(raw::Push {
+            values: vec![avm1_types::PushValue::Sint32(1)],
+          }),
+          cfg::Action::Stop,
+        ],
+        flow: CfgFlow::Simple(cfg::Simple { next: None }),
+      }),
+    };
+    assert_eq!(report.cfg, expected);
+    assert_eq!(report.diagnostics.len(), 1);
+    assert_eq!(report.diagnostics[0].offset(), 0);
+    assert!(report.errors.is_empty());
+
+    let report = parse_cfg_report(&input, &ParseOptions::default());
+    let expected = ParseError {
+      offset: 0,
+      code: 0x96,
+      field: "values",
+      kind: ParseErrorKind::UnknownPushValueType(0x0a),
+    };
+    assert_eq!(
+      report.errors,
+      BTreeMap::from([(CfgLabel(String::from("l0_0")), expected)])
+    );
+  }
+
+  #[test]
+  fn test_source_map() {
+    // Play; Jump +1; Stop; End
+    let input = [0x06, 0x99, 0x02, 0x00, 0x01, 0x00, 0x07, 0x00];
+    let report = parse_cfg_report(&input, &ParseOptions::default());
+    let mut expected = SourceMap::new();
+    expected.blocks.insert(
+      CfgLabel(String::from("l0_0")),
+      BlockSpan {
+        range: 0..6,
+        actions: vec![Range { start: 0, end: 1 }],
+        flow: Some(1..6),
+        overlapping: false,
+        unreachable: false,
+      },
+    );
+    expected.blocks.insert(
+      CfgLabel(String::from("l0_7")),
+      BlockSpan {
+        range: 7..8,
+        actions: Vec::new(),
+        flow: Some(7..8),
+        overlapping: false,
+        unreachable: false,
+      },
+    );
+    assert_eq!(report.source_map, expected);
+    assert_eq!(report.source_map.find_action(1), Some(&CfgLabel(String::from("l0_0"))));
+    assert_eq!(report.source_map.find_action(6), None);
+  }
+
+  #[test]
+  fn test_max_depth() {
+    // With { With { Stop } }
+    let input = [0x94, 0x02, 0x00, 0x06, 0x00, 0x94, 0x02, 0x00, 0x01, 0x00, 0x07];
+    let options = ParseOptions {
+      limits: ParseLimits {
+        max_depth: Some(1),
+        ..ParseLimits::default()
+      },
+      ..ParseOptions::default()
+    };
+    let actual = parse_cfg_with(&input, &options);
+    let error = ParseError {
+      offset: 5,
+      code: 0x94,
+      field: "body",
+      kind: ParseErrorKind::NestingLimitExceeded,
+    };
+    let expected = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label: CfgLabel(String::from("l0_0")),
+        actions: Vec::new(),
+        flow: CfgFlow::With(cfg::With {
+          body: Cfg {
+            blocks: Vec1::new(CfgBlock {
+              label: CfgLabel(String::from("l1_5")),
+              actions: Vec::new(),
+              flow: CfgFlow::Error(cfg::Error {
+                error: Some(error.into()),
+              }),
+            }),
+          },
+        }),
+      }),
+    };
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_max_depth_deep_input() {
+    const DEPTH: usize = 10000;
+    let mut input: Vec<u8> = Vec::new();
+    for i in 0..DEPTH {
+      let size = u16::try_from((DEPTH - i - 1) * 5 + 1).unwrap();
+      input.extend_from_slice(&[0x94, 0x02, 0x00]);
+      input.extend_from_slice(&size.to_le_bytes());
+    }
+    input.push(0x07);
+    let mut cfg = parse_cfg(&input);
+    let mut depth: usize = 0;
+    while let CfgFlow::With(with) = cfg.blocks.first().flow.clone() {
+      cfg = with.body;
+      depth += 1;
+    }
+    assert_eq!(depth, ParseLimits::DEFAULT_MAX_DEPTH);
+    assert!(matches!(cfg.blocks.first().flow, CfgFlow::Error(_)));
+  }
+
+  #[test]
+  fn test_oversized_bodies() {
+    // With { Stop } declaring 4 bytes
+    let input = [0x94, 0x02, 0x00, 0x04, 0x00, 0x07];
+    let diagnostic = Diagnostic::OversizedBody {
+      offset: 0,
+      code: 0x94,
+      declared: 4,
+      available: 1,
+    };
+
+    let clamp = ParseOptions {
+      oversized_bodies: OversizedBodyPolicy::Clamp,
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &clamp);
+    let expected = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label: CfgLabel(String::from("l0_0")),
+        actions: Vec::new(),
+        flow: CfgFlow::With(cfg::With {
+          body: Cfg {
+            blocks: Vec1::new(CfgBlock {
+              label: CfgLabel(String::from("l1_5")),
+              actions: vec![cfg::Action::Stop],
+              flow: CfgFlow::Simple(cfg::Simple { next: None }),
+            }),
+          },
+        }),
+      }),
+    };
+    assert_eq!(report.cfg, expected);
+    assert_eq!(report.diagnostics, vec![diagnostic.clone()]);
+
+    let error = ParseOptions {
+      oversized_bodies: OversizedBodyPolicy::Error,
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &error);
+    let error = ParseError {
+      offset: 0,
+      code: 0x94,
+      field: "body",
+      kind: ParseErrorKind::OversizedBody {
+        declared: 4,
+        available: 1,
+      },
+    };
+    let expected = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label: CfgLabel(String::from("l0_0")),
+        actions: Vec::new(),
+        flow: CfgFlow::Error(cfg::Error {
+          error: Some(error.into()),
+        }),
+      }),
+    };
+    assert_eq!(report.cfg, expected);
+    assert_eq!(report.errors, BTreeMap::from([(CfgLabel(String::from("l0_0")), error)]));
+    assert_eq!(report.diagnostics, vec![diagnostic]);
+  }
+
+  #[test]
+  fn test_wait_for_frame_skip_out_of_bounds() {
+    // WaitForFrame(frame: 1, skip: 5); Stop
+    let input = [0x8a, 0x03, 0x00, 0x01, 0x00, 0x05, 0x07];
+    let report = parse_cfg_report(&input, &ParseOptions::default());
+    let expected = Cfg {
+      blocks: Vec1::try_from_vec(vec![
+        CfgBlock {
+          label: CfgLabel(String::from("l0_0")),
+          actions: Vec::new(),
+          flow: CfgFlow::WaitForFrame(cfg::WaitForFrame {
+            frame: 1,
+            loading_target: None,
+            ready_target: Some(CfgLabel(String::from("l0_6"))),
+          }),
+        },
+        CfgBlock {
+          label: CfgLabel(String::from("l0_6")),
+          actions: vec![cfg::Action::Stop],
+          flow: CfgFlow::Simple(cfg::Simple { next: None }),
+        },
+      ])
+      .unwrap(),
+    };
+    assert_eq!(report.cfg, expected);
+    assert_eq!(
+      report.diagnostics,
+      vec![Diagnostic::SkipOutOfBounds {
+        offset: 0,
+        code: 0x8a,
+        skip: 5,
+        skipped: 1,
+      }]
+    );
+  }
+
+  #[test]
+  fn test_overlapping_code() {
+    // If(-3), jumping to the high byte of its length (decoded as End); Stop
+    let input = [0x9d, 0x02, 0x00, 0xfd, 0xff, 0x07];
+    let report = parse_cfg_report(&input, &ParseOptions::default());
+    let source_map = &report.source_map;
+    let overlapping: Vec<&str> = source_map
+      .blocks
+      .iter()
+      .filter(|(_, block)| block.overlapping)
+      .map(|(label, _)| label.0.as_str())
+      .collect();
+    assert_eq!(overlapping, ["l0_0", "l0_2"]);
+    let expected = Overlap {
+      first: ActionSpan {
+        label: CfgLabel(String::from("l0_0")),
+        range: 0..5,
+      },
+      second: ActionSpan {
+        label: CfgLabel(String::from("l0_2")),
+        range: 2..3,
+      },
+    };
+    assert_eq!(source_map.overlaps, vec![expected]);
+  }
+
+  #[test]
+  fn test_build_cfg() {
+    // Play; Jump(+1); Stop; Stop
+    let input = [0x06, 0x99, 0x02, 0x00, 0x01, 0x00, 0x07, 0x07];
+    let mut actions: Vec<(usize, usize, raw::Action)> = ActionReader::new(&input).collect();
+    assert_eq!(build_cfg(actions.clone()), parse_cfg(&input));
+
+    // Patch the jump to target the middle of its own encoding
+    actions[1].2 = raw::Action::Jump(raw::Jump { offset: -3 });
+    let cfg = build_cfg(actions);
+    let error = InvalidActionError {
+      message: String::from("no action at offset 3"),
+    };
+    assert_eq!(cfg.blocks.len(), 2);
+    assert_eq!(cfg.blocks[1].label, CfgLabel(String::from("l0_3")));
+    assert_eq!(cfg.blocks[1].flow, CfgFlow::Error(cfg::Error { error: Some(error) }));
+  }
+
+  #[test]
+  fn test_parse_cfg_lazy() {
+    // DefineFunction("", [], { With { Stop } }); With { Play }
+    let input = [
+      0x9b, 0x05, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x94, 0x02, 0x00, 0x01, 0x00, 0x07, 0x94, 0x02, 0x00, 0x01, 0x00,
+      0x06,
+    ];
+    let mut eager = parse_cfg(&input);
+    let lazy = parse_cfg_lazy(&input, &ParseOptions::default());
+
+    let label = CfgLabel(String::from("l1_8"));
+    assert_eq!(lazy.functions.keys().collect::<Vec<_>>(), [&label]);
+    let handle = &lazy.functions[&label];
+    assert_eq!(handle.range(), 8..14);
+
+    let body = match &mut eager.blocks[0].actions[0] {
+      cfg::Action::DefineFunction(function) => &mut function.body,
+      action => panic!("unexpected action {:?}", action),
+    };
+    let placeholder = Cfg {
+      blocks: Vec1::new(CfgBlock {
+        label,
+        actions: Vec::new(),
+        flow: CfgFlow::Simple(cfg::Simple { next: None }),
+      }),
+    };
+    let eager_body = core::mem::replace(body, placeholder);
+    let labels = |cfg: &Cfg| {
+      let mut blocks: Vec<&CfgBlock> = Vec::new();
+      crate::graph::collect_blocks(cfg, &mut blocks);
+      blocks.iter().map(|block| block.label.0.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(labels(&eager), ["l0_0", "l3_19"]);
+    assert_eq!(labels(&eager_body), ["l1_8", "l2_13"]);
+
+    assert_eq!(lazy.cfg, eager);
+    assert_eq!(handle.build(), eager_body);
+    assert_eq!(handle.build_lazy().cfg, eager_body);
+
+    // The skipped bodies are not decoded
+    let options = ParseOptions {
+      limits: ParseLimits {
+        max_actions: Some(4),
+        ..ParseLimits::default()
+      },
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &options);
+    assert!(!report.errors.is_empty());
+    assert_eq!(parse_cfg_lazy(&input, &options).cfg, lazy.cfg);
+  }
+
+  #[test]
+  fn test_keep_unreachable() {
+    // Jump(+1); Stop; End
+    let input = [0x99, 0x02, 0x00, 0x01, 0x00, 0x07, 0x00];
+    let options = ParseOptions {
+      keep_unreachable: true,
+      ..ParseOptions::default()
+    };
+    let report = parse_cfg_report(&input, &options);
+    let labels: Vec<&str> = report.cfg.blocks.iter().map(|block| block.label.0.as_str()).collect();
+    assert_eq!(labels, ["l0_0", "l0_6", "l1_5"]);
+    assert_eq!(
+      report.cfg.blocks[2],
+      CfgBlock {
+        label: CfgLabel(String::from("l1_5")),
+        actions: vec![cfg::Action::Stop],
+        flow: CfgFlow::Simple(cfg::Simple {
+          next: Some(CfgLabel(String::from("l0_6"))),
+        }),
+      }
+    );
+    let unreachable: Vec<&str> = report
+      .source_map
+      .blocks
+      .iter()
+      .filter(|(_, block)| block.unreachable)
+      .map(|(label, _)| label.0.as_str())
+      .collect();
+    assert_eq!(unreachable, ["l1_5"]);
+    assert_eq!(report.source_map.uncovered(input.len()), Vec::new());
+
+    // Jump(+1); Play; Stop; End
+    let input = [0x99, 0x02, 0x00, 0x01, 0x00, 0x06, 0x07, 0x00];
+    let report = parse_cfg_report(&input, &options);
+    let labels: Vec<&str> = report.cfg.blocks.iter().map(|block| block.label.0.as_str()).collect();
+    assert_eq!(labels, ["l0_0", "l0_6", "l1_5"]);
+    assert_eq!(
+      report.cfg.blocks[2],
+      CfgBlock {
+        label: CfgLabel(String::from("l1_5")),
+        actions: vec![cfg::Action::Play],
+        flow: CfgFlow::Simple(cfg::Simple {
+          next: Some(CfgLabel(String::from("l0_6"))),
+        }),
+      }
+    );
+
+    // Jump(+2); Return; Stop; End
+    let input = [0x99, 0x02, 0x00, 0x02, 0x00, 0x3e, 0x07, 0x00];
+    let report = parse_cfg_report(&input, &options);
+    let labels: Vec<&str> = report.cfg.blocks.iter().map(|block| block.label.0.as_str()).collect();
+    assert_eq!(labels, ["l0_0", "l0_7", "l1_5", "l1_6"]);
+
+    // DefineFunction("", [], { Jump(+1); Stop }); End
+    let input = [
+      0x9b, 0x05, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x99, 0x02, 0x00, 0x01, 0x00, 0x07, 0x00,
+    ];
+    let report = parse_cfg_report(&input, &options);
+    assert_eq!(report.cfg.blocks.len(), 1);
+    let body = match &report.cfg.blocks[0].actions[0] {
+      cfg::Action::DefineFunction(function) => &function.body,
+      action => panic!("unexpected action {:?}", action),
+    };
+    let labels: Vec<&str> = body.blocks.iter().map(|block| block.label.0.as_str()).collect();
+    assert_eq!(labels, ["l1_8", "l2_13"]);
+    assert!(report.source_map.blocks[&CfgLabel(String::from("l2_13"))].unreachable);
+  }
+}
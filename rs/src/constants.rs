@@ -0,0 +1,221 @@
+use crate::graph::{collect_blocks, FlowGraph};
+use avm1_types::cfg;
+use avm1_types::cfg::{Cfg, CfgBlock, CfgFlow, CfgLabel};
+use avm1_types::PushValue;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+/// `PushValue::Constant` value of a CFG, with its resolved string
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstantRef {
+  /// Block containing the `Push` action
+  pub label: CfgLabel,
+  /// Index of the `Push` action in the block
+  pub action: usize,
+  /// Index of the value in the `Push` action
+  pub value: usize,
+  /// Index in the constant pool
+  pub index: u16,
+  pub resolution: ConstantResolution,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstantResolution {
+  /// All the constant pools reaching the action define the constant with the same value.
+  Resolved(String),
+  /// The constant pools reaching the action disagree: the possible values, sorted, `None` if the
+  /// index is out of range of some pool.
+  Ambiguous(Vec<Option<String>>),
+  /// No constant pool reaching the action defines the constant.
+  OutOfRange,
+}
+
+/// Resolves the constant values pushed in the CFG (including nested functions), in block order.
+///
+/// The `ConstantPool` definitions are propagated through the control flow. Function bodies inherit
+/// the pools reaching their `DefineFunction` action. Exceptions may leave a `try` body after any of
+/// its actions: the pools defined in the `try` (or `catch`) body also reach the `catch` and
+/// `finally` bodies. Unreachable blocks are ignored.
+pub fn resolve_constants(cfg: &Cfg) -> Vec<ConstantRef> {
+  let mut resolver = Resolver {
+    pools: Vec::new(),
+    ids: HashMap::new(),
+    constants: Vec::new(),
+  };
+  resolver.resolve_function(cfg, BTreeSet::from([None]));
+  resolver.constants
+}
+
+/// Constant pools reaching a program point, `None` when no pool is defined
+type PoolSet = BTreeSet<Option<usize>>;
+
+struct Resolver<'a> {
+  pools: Vec<&'a [String]>,
+  ids: HashMap<(&'a CfgLabel, usize), usize>,
+  constants: Vec<ConstantRef>,
+}
+
+impl<'a> Resolver<'a> {
+  fn resolve_function(&mut self, body: &'a Cfg, entry: PoolSet) {
+    let graph = FlowGraph::new(body);
+    let mut blocks: Vec<&'a CfgBlock> = Vec::new();
+    collect_blocks(body, &mut blocks);
+    let blocks: HashMap<&CfgLabel, &'a CfgBlock> = blocks.into_iter().map(|block| (&block.label, block)).collect();
+    let handlers = exception_handlers(blocks.values().copied());
+
+    let mut states: HashMap<CfgLabel, PoolSet> = HashMap::new();
+    let mut queue: VecDeque<CfgLabel> = VecDeque::new();
+    states.insert(graph.entry().clone(), entry);
+    queue.push_back(graph.entry().clone());
+    while let Some(label) = queue.pop_front() {
+      let block = blocks[&label];
+      let mut state = states[&label].clone();
+      // Pools active at any point of the block
+      let mut seen = state.clone();
+      for (index, action) in block.actions.iter().enumerate() {
+        if let cfg::Action::ConstantPool(action) = action {
+          let id = self.pool_id(&block.label, index, &action.pool);
+          state = BTreeSet::from([Some(id)]);
+          seen.insert(Some(id));
+        }
+      }
+
+      let targets = graph.successors(&label).map(|target| (target, &state)).chain(
+        handlers
+          .get(&label)
+          .into_iter()
+          .flatten()
+          .map(|target| (*target, &seen)),
+      );
+      for (target, pools) in targets {
+        let target_state = states.entry(target.clone()).or_default();
+        if !pools.is_subset(target_state) {
+          target_state.extend(pools.iter().copied());
+          queue.push_back(target.clone());
+        }
+      }
+    }
+
+    for label in graph.labels() {
+      let (block, mut state) = match states.get(label) {
+        Some(state) => (blocks[label], state.clone()),
+        None => continue,
+      };
+      for (index, action) in block.actions.iter().enumerate() {
+        match action {
+          cfg::Action::ConstantPool(action) => {
+            state = BTreeSet::from([Some(self.pool_id(&block.label, index, &action.pool))]);
+          }
+          cfg::Action::Push(action) => {
+            for (value, push_value) in action.values.iter().enumerate() {
+              if let PushValue::Constant(constant) = push_value {
+                let resolution = self.resolve(&state, *constant);
+                self.constants.push(ConstantRef {
+                  label: block.label.clone(),
+                  action: index,
+                  value,
+                  index: *constant,
+                  resolution,
+                });
+              }
+            }
+          }
+          cfg::Action::DefineFunction(action) => self.resolve_function(&action.body, state.clone()),
+          cfg::Action::DefineFunction2(action) => self.resolve_function(&action.body, state.clone()),
+          _ => {}
+        }
+      }
+    }
+  }
+
+  fn pool_id(&mut self, label: &'a CfgLabel, action: usize, pool: &'a [String]) -> usize {
+    let pools = &mut self.pools;
+    *self.ids.entry((label, action)).or_insert_with(|| {
+      pools.push(pool);
+      pools.len() - 1
+    })
+  }
+
+  fn resolve(&self, state: &PoolSet, index: u16) -> ConstantResolution {
+    let candidates: BTreeSet<Option<&String>> = state
+      .iter()
+      .map(|pool| pool.and_then(|id| self.pools[id].get(usize::from(index))))
+      .collect();
+    let mut candidates = candidates.into_iter();
+    match (candidates.next(), candidates.len()) {
+      (Some(Some(value)), 0) => ConstantResolution::Resolved(value.clone()),
+      (Some(None), 0) | (None, _) => ConstantResolution::OutOfRange,
+      (Some(first), _) => {
+        let values = Some(first).into_iter().chain(candidates);
+        ConstantResolution::Ambiguous(values.map(|value| value.cloned()).collect())
+      }
+    }
+  }
+}
+
+/// Returns the handler entries (`catch`, then `finally`) reachable by an exception from each block.
+fn exception_handlers<'a>(blocks: impl Iterator<Item = &'a CfgBlock>) -> HashMap<&'a CfgLabel, Vec<&'a CfgLabel>> {
+  let mut handlers: HashMap<&'a CfgLabel, Vec<&'a CfgLabel>> = HashMap::new();
+  for block in blocks {
+    let flow = match &block.flow {
+      CfgFlow::Try(flow) => flow,
+      _ => continue,
+    };
+    let catch = flow.catch.as_ref().map(|catch| &catch.body);
+    let finally = flow.finally.as_ref();
+    let mut guarded: Vec<(&Cfg, Vec<&CfgLabel>)> = Vec::new();
+    let entries: Vec<&CfgLabel> = catch
+      .iter()
+      .chain(finally.iter())
+      .map(|body| &body.blocks.first().label)
+      .collect();
+    guarded.push((&flow.r#try, entries));
+    if let (Some(catch), Some(finally)) = (catch, finally) {
+      guarded.push((catch, vec![&finally.blocks.first().label]));
+    }
+    for (body, entries) in guarded {
+      let mut body_blocks: Vec<&CfgBlock> = Vec::new();
+      collect_blocks(body, &mut body_blocks);
+      for body_block in body_blocks {
+        handlers
+          .entry(&body_block.label)
+          .or_default()
+          .extend(entries.iter().copied());
+      }
+    }
+  }
+  handlers
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg;
+
+  #[test]
+  fn test_resolve_constants() {
+    // ConstantPool("a"); Push(c0, true); If(+7); ConstantPool("b"); Push(c0, c1);
+    // DefineFunction("") { Push(c0) }
+    let input = [
+      0x88, 0x04, 0x00, 0x01, 0x00, 0x61, 0x00, 0x96, 0x04, 0x00, 0x08, 0x00, 0x05, 0x01, 0x9d, 0x02, 0x00, 0x07, 0x00,
+      0x88, 0x04, 0x00, 0x01, 0x00, 0x62, 0x00, 0x96, 0x04, 0x00, 0x08, 0x00, 0x08, 0x01, 0x9b, 0x05, 0x00, 0x00, 0x00,
+      0x00, 0x05, 0x00, 0x96, 0x02, 0x00, 0x08, 0x00,
+    ];
+    let ambiguous = ConstantResolution::Ambiguous(vec![Some(String::from("a")), Some(String::from("b"))]);
+    let constant = |label: &str, action: usize, value: usize, index: u16, resolution: ConstantResolution| ConstantRef {
+      label: CfgLabel(String::from(label)),
+      action,
+      value,
+      index,
+      resolution,
+    };
+    assert_eq!(
+      resolve_constants(&parse_cfg(&input)),
+      [
+        constant("l0_0", 1, 0, 0, ConstantResolution::Resolved(String::from("a"))),
+        constant("l0_26", 0, 0, 0, ambiguous.clone()),
+        constant("l0_26", 0, 1, 1, ConstantResolution::OutOfRange),
+        constant("l1_41", 0, 0, 0, ambiguous),
+      ]
+    );
+  }
+}
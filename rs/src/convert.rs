@@ -0,0 +1,468 @@
+use crate::reader::ActionReader;
+use avm1_types::cfg;
+use avm1_types::raw;
+use core::convert::TryFrom;
+use core::ops::Range;
+
+/// Control flow class of a raw action
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ActionClass {
+  /// The action is always followed by the same action (simple actions and function definitions)
+  Linear,
+  /// The action transfers the control to a target depending on its operands, or to its code
+  /// blocks (`If`, `Jump`, `Try`, `WaitForFrame`, `WaitForFrame2`, `With`)
+  Branching,
+  /// The action ends the execution of the current code block (`End`, `Error`, `Return`, `Throw`)
+  Terminating,
+}
+
+/// Control flow of a raw action, as used by the CFG parser
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionSuccessors {
+  pub class: ActionClass,
+  /// Offsets of the actions that may be executed next, `None` for targets that can't be resolved.
+  ///
+  /// - Linear actions: the next action, after the function body for function definitions.
+  /// - `If`: the jump target, then the next action.
+  /// - `Jump`: the jump target.
+  /// - `WaitForFrame` and `WaitForFrame2`: the action reached after the skipped actions, then the
+  ///   next action.
+  /// - `Try` and `With`: the start of their first code block, then the action following their
+  ///   code blocks.
+  pub successors: Vec<Option<usize>>,
+  /// Ranges of the code blocks of the action, parsed as nested CFGs: function body, `try`,
+  /// `catch` and `finally` blocks, or `with` body.
+  pub bodies: Vec<Range<usize>>,
+}
+
+/// Returns the control flow of `action`, decoded from `len` bytes at `offset` in `reader`.
+pub fn action_successors(reader: &ActionReader, offset: usize, len: usize, action: &raw::Action) -> ActionSuccessors {
+  successors_with(offset, len, action, |offset, count| reader.skip_from(offset, count))
+}
+
+/// Returns the control flow of `action`, see `action_successors`.
+///
+/// `skip_from(offset, count)` returns the offset reached after skipping `count` actions from
+/// `offset`, for the `WaitForFrame` targets.
+pub(crate) fn successors_with(
+  offset: usize,
+  len: usize,
+  action: &raw::Action,
+  skip_from: impl FnOnce(usize, usize) -> Option<usize>,
+) -> ActionSuccessors {
+  let end_offset = offset + len;
+  let (class, successors, bodies) = match action {
+    raw::Action::DefineFunction(action) => {
+      let body = end_offset..(end_offset + usize::from(action.body_size));
+      (ActionClass::Linear, vec![Some(body.end)], vec![body])
+    }
+    raw::Action::DefineFunction2(action) => {
+      let body = end_offset..(end_offset + usize::from(action.body_size));
+      (ActionClass::Linear, vec![Some(body.end)], vec![body])
+    }
+    raw::Action::End | raw::Action::Error(_) | raw::Action::Return | raw::Action::Throw => {
+      (ActionClass::Terminating, Vec::new(), Vec::new())
+    }
+    raw::Action::If(action) => {
+      let targets = vec![try_add_offset(end_offset, action.offset), Some(end_offset)];
+      (ActionClass::Branching, targets, Vec::new())
+    }
+    raw::Action::Jump(action) => {
+      let targets = vec![try_add_offset(end_offset, action.offset)];
+      (ActionClass::Branching, targets, Vec::new())
+    }
+    raw::Action::Try(action) => {
+      let mut bodies: Vec<Range<usize>> = Vec::new();
+      let mut start = end_offset;
+      let sizes = [
+        Some(action.r#try),
+        action.catch.as_ref().map(|c| c.size),
+        action.finally,
+      ];
+      for size in sizes.iter().flatten() {
+        let end = start + usize::from(*size);
+        bodies.push(start..end);
+        start = end;
+      }
+      (ActionClass::Branching, vec![Some(end_offset), Some(start)], bodies)
+    }
+    raw::Action::WaitForFrame(action) => {
+      let loading_target = skip_from(end_offset, usize::from(action.skip));
+      (
+        ActionClass::Branching,
+        vec![loading_target, Some(end_offset)],
+        Vec::new(),
+      )
+    }
+    raw::Action::WaitForFrame2(action) => {
+      let loading_target = skip_from(end_offset, usize::from(action.skip));
+      (
+        ActionClass::Branching,
+        vec![loading_target, Some(end_offset)],
+        Vec::new(),
+      )
+    }
+    raw::Action::With(action) => {
+      let body = end_offset..(end_offset + usize::from(action.size));
+      (
+        ActionClass::Branching,
+        vec![Some(end_offset), Some(body.end)],
+        vec![body],
+      )
+    }
+    _ => (ActionClass::Linear, vec![Some(end_offset)], Vec::new()),
+  };
+  ActionSuccessors {
+    class,
+    successors,
+    bodies,
+  }
+}
+
+/// Raw action without a CFG action counterpart, returned by `convert_action`
+///
+/// Function definitions hold their body as a nested CFG and the other actions are represented by
+/// the `CfgFlow` ending a block: they are converted by the CFG parser.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FromRawActionError {
+  DefineFunction(Box<raw::DefineFunction>),
+  DefineFunction2(Box<raw::DefineFunction2>),
+  End,
+  Error(raw::Error),
+  If(raw::If),
+  Jump(raw::Jump),
+  Return,
+  Throw,
+  Try(Box<raw::Try>),
+  WaitForFrame(raw::WaitForFrame),
+  WaitForFrame2(raw::WaitForFrame2),
+  With(raw::With),
+}
+
+impl From<FromRawActionError> for raw::Action {
+  fn from(value: FromRawActionError) -> Self {
+    match value {
+      FromRawActionError::DefineFunction(action) => raw::Action::DefineFunction(action),
+      FromRawActionError::DefineFunction2(action) => raw::Action::DefineFunction2(action),
+      FromRawActionError::End => raw::Action::End,
+      FromRawActionError::Error(action) => raw::Action::Error(action),
+      FromRawActionError::If(action) => raw::Action::If(action),
+      FromRawActionError::Jump(action) => raw::Action::Jump(action),
+      FromRawActionError::Return => raw::Action::Return,
+      FromRawActionError::Throw => raw::Action::Throw,
+      FromRawActionError::Try(action) => raw::Action::Try(action),
+      FromRawActionError::WaitForFrame(action) => raw::Action::WaitForFrame(action),
+      FromRawActionError::WaitForFrame2(action) => raw::Action::WaitForFrame2(action),
+      FromRawActionError::With(action) => raw::Action::With(action),
+    }
+  }
+}
+
+/// Converts a linear raw action without code blocks to its CFG representation.
+///
+/// Function definitions and actions without a linear flow are returned as a
+/// `FromRawActionError`: these actions are converted by the CFG parser.
+pub fn convert_action(action: raw::Action) -> Result<cfg::Action, FromRawActionError> {
+  match action {
+    raw::Action::Add => Ok(cfg::Action::Add),
+    raw::Action::Add2 => Ok(cfg::Action::Add2),
+    raw::Action::And => Ok(cfg::Action::And),
+    raw::Action::AsciiToChar => Ok(cfg::Action::AsciiToChar),
+    raw::Action::BitAnd => Ok(cfg::Action::BitAnd),
+    raw::Action::BitOr => Ok(cfg::Action::BitOr),
+    raw::Action::BitLShift => Ok(cfg::Action::BitLShift),
+    raw::Action::BitRShift => Ok(cfg::Action::BitRShift),
+    raw::Action::BitURShift => Ok(cfg::Action::BitURShift),
+    raw::Action::BitXor => Ok(cfg::Action::BitXor),
+    raw::Action::Call => Ok(cfg::Action::Call),
+    raw::Action::CallFunction => Ok(cfg::Action::CallFunction),
+    raw::Action::CallMethod => Ok(cfg::Action::CallMethod),
+    raw::Action::CharToAscii => Ok(cfg::Action::CharToAscii),
+    raw::Action::CastOp => Ok(cfg::Action::CastOp),
+    raw::Action::CloneSprite => Ok(cfg::Action::CloneSprite),
+    raw::Action::ConstantPool(action) => Ok(cfg::Action::ConstantPool(action)),
+    raw::Action::Decrement => Ok(cfg::Action::Decrement),
+    raw::Action::DefineLocal => Ok(cfg::Action::DefineLocal),
+    raw::Action::DefineLocal2 => Ok(cfg::Action::DefineLocal2),
+    raw::Action::Delete => Ok(cfg::Action::Delete),
+    raw::Action::Delete2 => Ok(cfg::Action::Delete2),
+    raw::Action::Divide => Ok(cfg::Action::Divide),
+    raw::Action::EndDrag => Ok(cfg::Action::EndDrag),
+    raw::Action::Enumerate => Ok(cfg::Action::Enumerate),
+    raw::Action::Enumerate2 => Ok(cfg::Action::Enumerate2),
+    raw::Action::Equals => Ok(cfg::Action::Equals),
+    raw::Action::Equals2 => Ok(cfg::Action::Equals2),
+    raw::Action::Extends => Ok(cfg::Action::Extends),
+    raw::Action::FsCommand2 => Ok(cfg::Action::FsCommand2),
+    raw::Action::GetMember => Ok(cfg::Action::GetMember),
+    raw::Action::GetProperty => Ok(cfg::Action::GetProperty),
+    raw::Action::GetTime => Ok(cfg::Action::GetTime),
+    raw::Action::GetUrl(action) => Ok(cfg::Action::GetUrl(action)),
+    raw::Action::GetUrl2(action) => Ok(cfg::Action::GetUrl2(action)),
+    raw::Action::GetVariable => Ok(cfg::Action::GetVariable),
+    raw::Action::GotoFrame(action) => Ok(cfg::Action::GotoFrame(action)),
+    raw::Action::GotoFrame2(action) => Ok(cfg::Action::GotoFrame2(action)),
+    raw::Action::GotoLabel(action) => Ok(cfg::Action::GotoLabel(action)),
+    raw::Action::Greater => Ok(cfg::Action::Greater),
+    raw::Action::ImplementsOp => Ok(cfg::Action::ImplementsOp),
+    raw::Action::Increment => Ok(cfg::Action::Increment),
+    raw::Action::InitArray => Ok(cfg::Action::InitArray),
+    raw::Action::InitObject => Ok(cfg::Action::InitObject),
+    raw::Action::InstanceOf => Ok(cfg::Action::InstanceOf),
+    raw::Action::Less => Ok(cfg::Action::Less),
+    raw::Action::Less2 => Ok(cfg::Action::Less2),
+    raw::Action::MbAsciiToChar => Ok(cfg::Action::MbAsciiToChar),
+    raw::Action::MbCharToAscii => Ok(cfg::Action::MbCharToAscii),
+    raw::Action::MbStringExtract => Ok(cfg::Action::MbStringExtract),
+    raw::Action::MbStringLength => Ok(cfg::Action::MbStringLength),
+    raw::Action::Modulo => Ok(cfg::Action::Modulo),
+    raw::Action::Multiply => Ok(cfg::Action::Multiply),
+    raw::Action::NewMethod => Ok(cfg::Action::NewMethod),
+    raw::Action::NewObject => Ok(cfg::Action::NewObject),
+    raw::Action::NextFrame => Ok(cfg::Action::NextFrame),
+    raw::Action::Not => Ok(cfg::Action::Not),
+    raw::Action::Or => Ok(cfg::Action::Or),
+    raw::Action::Play => Ok(cfg::Action::Play),
+    raw::Action::Pop => Ok(cfg::Action::Pop),
+    raw::Action::PrevFrame => Ok(cfg::Action::PrevFrame),
+    raw::Action::Push(action) => Ok(cfg::Action::Push(action)),
+    raw::Action::PushDuplicate => Ok(cfg::Action::PushDuplicate),
+    raw::Action::RandomNumber => Ok(cfg::Action::RandomNumber),
+    raw::Action::RemoveSprite => Ok(cfg::Action::RemoveSprite),
+    raw::Action::SetMember => Ok(cfg::Action::SetMember),
+    raw::Action::SetProperty => Ok(cfg::Action::SetProperty),
+    raw::Action::SetTarget(action) => Ok(cfg::Action::SetTarget(action)),
+    raw::Action::SetTarget2 => Ok(cfg::Action::SetTarget2),
+    raw::Action::SetVariable => Ok(cfg::Action::SetVariable),
+    raw::Action::StackSwap => Ok(cfg::Action::StackSwap),
+    raw::Action::StartDrag => Ok(cfg::Action::StartDrag),
+    raw::Action::Stop => Ok(cfg::Action::Stop),
+    raw::Action::StopSounds => Ok(cfg::Action::StopSounds),
+    raw::Action::StoreRegister(action) => Ok(cfg::Action::StoreRegister(action)),
+    raw::Action::StrictEquals => Ok(cfg::Action::StrictEquals),
+    raw::Action::StrictMode(action) => Ok(cfg::Action::StrictMode(action)),
+    raw::Action::StringAdd => Ok(cfg::Action::StringAdd),
+    raw::Action::StringEquals => Ok(cfg::Action::StringEquals),
+    raw::Action::StringExtract => Ok(cfg::Action::StringExtract),
+    raw::Action::StringGreater => Ok(cfg::Action::StringGreater),
+    raw::Action::StringLength => Ok(cfg::Action::StringLength),
+    raw::Action::StringLess => Ok(cfg::Action::StringLess),
+    raw::Action::Subtract => Ok(cfg::Action::Subtract),
+    raw::Action::TargetPath => Ok(cfg::Action::TargetPath),
+    raw::Action::ToInteger => Ok(cfg::Action::ToInteger),
+    raw::Action::ToNumber => Ok(cfg::Action::ToNumber),
+    raw::Action::ToString => Ok(cfg::Action::ToString),
+    raw::Action::ToggleQuality => Ok(cfg::Action::ToggleQuality),
+    raw::Action::Trace => Ok(cfg::Action::Trace),
+    raw::Action::TypeOf => Ok(cfg::Action::TypeOf),
+    raw::Action::Raw(action) => Ok(cfg::Action::Raw(action)),
+    raw::Action::DefineFunction(action) => Err(FromRawActionError::DefineFunction(action)),
+    raw::Action::DefineFunction2(action) => Err(FromRawActionError::DefineFunction2(action)),
+    raw::Action::End => Err(FromRawActionError::End),
+    raw::Action::Error(action) => Err(FromRawActionError::Error(action)),
+    raw::Action::If(action) => Err(FromRawActionError::If(action)),
+    raw::Action::Jump(action) => Err(FromRawActionError::Jump(action)),
+    raw::Action::Return => Err(FromRawActionError::Return),
+    raw::Action::Throw => Err(FromRawActionError::Throw),
+    raw::Action::Try(action) => Err(FromRawActionError::Try(action)),
+    raw::Action::WaitForFrame(action) => Err(FromRawActionError::WaitForFrame(action)),
+    raw::Action::WaitForFrame2(action) => Err(FromRawActionError::WaitForFrame2(action)),
+    raw::Action::With(action) => Err(FromRawActionError::With(action)),
+  }
+}
+
+/// Returns the code of the action, or `None` for `Error` actions.
+pub fn action_code(action: &raw::Action) -> Option<u8> {
+  let code: u8 = match action {
+    raw::Action::End => 0x00,
+    raw::Action::NextFrame => 0x04,
+    raw::Action::PrevFrame => 0x05,
+    raw::Action::Play => 0x06,
+    raw::Action::Stop => 0x07,
+    raw::Action::ToggleQuality => 0x08,
+    raw::Action::StopSounds => 0x09,
+    raw::Action::Add => 0x0a,
+    raw::Action::Subtract => 0x0b,
+    raw::Action::Multiply => 0x0c,
+    raw::Action::Divide => 0x0d,
+    raw::Action::Equals => 0x0e,
+    raw::Action::Less => 0x0f,
+    raw::Action::And => 0x10,
+    raw::Action::Or => 0x11,
+    raw::Action::Not => 0x12,
+    raw::Action::StringEquals => 0x13,
+    raw::Action::StringLength => 0x14,
+    raw::Action::StringExtract => 0x15,
+    raw::Action::Pop => 0x17,
+    raw::Action::ToInteger => 0x18,
+    raw::Action::GetVariable => 0x1c,
+    raw::Action::SetVariable => 0x1d,
+    raw::Action::SetTarget2 => 0x20,
+    raw::Action::StringAdd => 0x21,
+    raw::Action::GetProperty => 0x22,
+    raw::Action::SetProperty => 0x23,
+    raw::Action::CloneSprite => 0x24,
+    raw::Action::RemoveSprite => 0x25,
+    raw::Action::Trace => 0x26,
+    raw::Action::StartDrag => 0x27,
+    raw::Action::EndDrag => 0x28,
+    raw::Action::StringLess => 0x29,
+    raw::Action::Throw => 0x2a,
+    raw::Action::CastOp => 0x2b,
+    raw::Action::ImplementsOp => 0x2c,
+    raw::Action::FsCommand2 => 0x2d,
+    raw::Action::RandomNumber => 0x30,
+    raw::Action::MbStringLength => 0x31,
+    raw::Action::CharToAscii => 0x32,
+    raw::Action::AsciiToChar => 0x33,
+    raw::Action::GetTime => 0x34,
+    raw::Action::MbStringExtract => 0x35,
+    raw::Action::MbCharToAscii => 0x36,
+    raw::Action::MbAsciiToChar => 0x37,
+    raw::Action::Delete => 0x3a,
+    raw::Action::Delete2 => 0x3b,
+    raw::Action::DefineLocal => 0x3c,
+    raw::Action::CallFunction => 0x3d,
+    raw::Action::Return => 0x3e,
+    raw::Action::Modulo => 0x3f,
+    raw::Action::NewObject => 0x40,
+    raw::Action::DefineLocal2 => 0x41,
+    raw::Action::InitArray => 0x42,
+    raw::Action::InitObject => 0x43,
+    raw::Action::TypeOf => 0x44,
+    raw::Action::TargetPath => 0x45,
+    raw::Action::Enumerate => 0x46,
+    raw::Action::Add2 => 0x47,
+    raw::Action::Less2 => 0x48,
+    raw::Action::Equals2 => 0x49,
+    raw::Action::ToNumber => 0x4a,
+    raw::Action::ToString => 0x4b,
+    raw::Action::PushDuplicate => 0x4c,
+    raw::Action::StackSwap => 0x4d,
+    raw::Action::GetMember => 0x4e,
+    raw::Action::SetMember => 0x4f,
+    raw::Action::Increment => 0x50,
+    raw::Action::Decrement => 0x51,
+    raw::Action::CallMethod => 0x52,
+    raw::Action::NewMethod => 0x53,
+    raw::Action::InstanceOf => 0x54,
+    raw::Action::Enumerate2 => 0x55,
+    raw::Action::BitAnd => 0x60,
+    raw::Action::BitOr => 0x61,
+    raw::Action::BitXor => 0x62,
+    raw::Action::BitLShift => 0x63,
+    raw::Action::BitRShift => 0x64,
+    raw::Action::BitURShift => 0x65,
+    raw::Action::StrictEquals => 0x66,
+    raw::Action::Greater => 0x67,
+    raw::Action::StringGreater => 0x68,
+    raw::Action::Extends => 0x69,
+    raw::Action::GotoFrame(_) => 0x81,
+    raw::Action::GetUrl(_) => 0x83,
+    raw::Action::StoreRegister(_) => 0x87,
+    raw::Action::ConstantPool(_) => 0x88,
+    raw::Action::StrictMode(_) => 0x89,
+    raw::Action::WaitForFrame(_) => 0x8a,
+    raw::Action::SetTarget(_) => 0x8b,
+    raw::Action::GotoLabel(_) => 0x8c,
+    raw::Action::WaitForFrame2(_) => 0x8d,
+    raw::Action::DefineFunction2(_) => 0x8e,
+    raw::Action::Try(_) => 0x8f,
+    raw::Action::With(_) => 0x94,
+    raw::Action::Push(_) => 0x96,
+    raw::Action::Jump(_) => 0x99,
+    raw::Action::GetUrl2(_) => 0x9a,
+    raw::Action::DefineFunction(_) => 0x9b,
+    raw::Action::If(_) => 0x9d,
+    raw::Action::Call => 0x9e,
+    raw::Action::GotoFrame2(_) => 0x9f,
+    raw::Action::Raw(action) => action.code,
+    raw::Action::Error(_) => return None,
+  };
+  Some(code)
+}
+
+/// Returns the offset `right` bytes away from `left`, or `None` if it overflows.
+pub(crate) fn try_add_offset(left: usize, right: i16) -> Option<usize> {
+  if right >= 0 {
+    let right_u16: u16 = u16::try_from(right).unwrap();
+    let right_usize = usize::from(right_u16);
+    left.checked_add(right_usize)
+  } else {
+    let right_u16: u16 = u16::try_from(-right).unwrap();
+    let right_usize = usize::from(right_u16);
+    left.checked_sub(right_usize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use avm1_types::CatchTarget;
+
+  #[test]
+  fn test_action_successors() {
+    // Stop; If(+1); WaitForFrame(frame: 0, skip: 1); Stop; Return
+    let input = [
+      0x07, 0x9d, 0x02, 0x00, 0x01, 0x00, 0x8a, 0x03, 0x00, 0x00, 0x00, 0x01, 0x07, 0x3e,
+    ];
+    let reader = ActionReader::new(&input);
+    let actions: Vec<ActionSuccessors> = reader
+      .map(|(offset, len, action)| action_successors(&reader, offset, len, &action))
+      .collect();
+    let linear = |next: usize| ActionSuccessors {
+      class: ActionClass::Linear,
+      successors: vec![Some(next)],
+      bodies: Vec::new(),
+    };
+    let branching = |successors: Vec<Option<usize>>| ActionSuccessors {
+      class: ActionClass::Branching,
+      successors,
+      bodies: Vec::new(),
+    };
+    let expected = vec![
+      linear(1),
+      branching(vec![Some(7), Some(6)]),
+      branching(vec![Some(13), Some(12)]),
+      linear(13),
+      ActionSuccessors {
+        class: ActionClass::Terminating,
+        successors: Vec::new(),
+        bodies: Vec::new(),
+      },
+    ];
+    assert_eq!(actions, expected);
+
+    // Try { try: 2, catch(r0): 3 } at offset 0, encoded in 10 bytes
+    let r#try = raw::Action::Try(Box::new(raw::Try {
+      r#try: 2,
+      catch: Some(raw::CatchBlock {
+        target: CatchTarget::Register(0),
+        size: 3,
+      }),
+      finally: None,
+    }));
+    assert_eq!(
+      action_successors(&reader, 0, 10, &r#try),
+      ActionSuccessors {
+        class: ActionClass::Branching,
+        successors: vec![Some(10), Some(15)],
+        bodies: vec![10..12, 12..15],
+      }
+    );
+  }
+
+  #[test]
+  fn test_convert_action() {
+    assert_eq!(convert_action(raw::Action::Stop), Ok(cfg::Action::Stop));
+    let jump = raw::Jump { offset: 0 };
+    assert_eq!(
+      convert_action(raw::Action::Jump(jump)),
+      Err(FromRawActionError::Jump(jump))
+    );
+    assert_eq!(
+      raw::Action::from(FromRawActionError::Jump(jump)),
+      raw::Action::Jump(jump)
+    );
+  }
+}
@@ -0,0 +1,100 @@
+use crate::cfg::CfgReport;
+use crate::graph::{collect_blocks, function_bodies};
+use crate::options::ParseOptions;
+use crate::reader::ActionReader;
+use avm1_types::cfg::{Cfg, CfgBlock, CfgFlow};
+use avm1_types::raw;
+use core::ops::Range;
+
+/// Byte range not covered by any reachable action
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadCode {
+  pub range: Range<usize>,
+  /// The range starts where the CFG parser stopped at an `Error` flow (invalid action, action
+  /// limit, nesting limit): it was not explored, so it may still be reachable.
+  pub unexplored: bool,
+  /// Best-effort linear decoding of the range, as `(offset, len, action)` tuples.
+  ///
+  /// The decoding stops at the end of the range: an action crossing it is decoded as an error.
+  pub actions: Vec<(usize, usize, raw::Action)>,
+}
+
+/// Returns the byte ranges of `avm1` that are not reached by the CFG parser, sorted by offset.
+///
+/// `report` is the result of parsing `avm1` with `options` (see `parse_cfg_report`). Orphan blocks
+/// kept with `ParseOptions::keep_unreachable` are reported as dead code.
+pub fn find_dead_code(avm1: &[u8], options: &ParseOptions, report: &CfgReport) -> Vec<DeadCode> {
+  let mut reachable = report.source_map.clone();
+  reachable.blocks.retain(|_, block| !block.unreachable);
+
+  let mut error_blocks: Vec<&CfgBlock> = Vec::new();
+  collect_error_blocks(&report.cfg, &mut error_blocks);
+  let error_ends: Vec<usize> = error_blocks
+    .iter()
+    .filter_map(|block| reachable.blocks.get(&block.label))
+    .map(|block| block.range.end)
+    .collect();
+
+  reachable
+    .uncovered(avm1.len())
+    .into_iter()
+    .map(|range| {
+      let mut reader = ActionReader::with_options(&avm1[..range.end], *options);
+      reader.seek(range.start);
+      DeadCode {
+        unexplored: error_ends.iter().any(|end| range.contains(end)),
+        actions: reader.collect(),
+        range,
+      }
+    })
+    .collect()
+}
+
+/// Collects the blocks of `cfg` (including nested functions) ending with an `Error` flow.
+fn collect_error_blocks<'a>(cfg: &'a Cfg, error_blocks: &mut Vec<&'a CfgBlock>) {
+  let mut blocks: Vec<&CfgBlock> = Vec::new();
+  collect_blocks(cfg, &mut blocks);
+  error_blocks.extend(
+    blocks
+      .into_iter()
+      .filter(|block| matches!(block.flow, CfgFlow::Error(_))),
+  );
+  for body in function_bodies(cfg) {
+    collect_error_blocks(body, error_blocks);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg_report;
+  use crate::options::ParseLimits;
+
+  #[test]
+  fn test_find_dead_code() {
+    // Jump(+2); Stop; Push (truncated); End
+    let input = [0x99, 0x02, 0x00, 0x02, 0x00, 0x07, 0x96, 0x00];
+    let options = ParseOptions::default();
+    let actual = find_dead_code(&input, &options, &parse_cfg_report(&input, &options));
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].range, 5..7);
+    assert!(!actual[0].unexplored);
+    assert_eq!(actual[0].actions.len(), 2);
+    assert_eq!(actual[0].actions[0], (5, 1, raw::Action::Stop));
+    assert!(matches!(actual[0].actions[1], (6, 1, raw::Action::Error(_))));
+
+    // Stop; Play; Stop, with the action limit reached after the first action
+    let input = [0x07, 0x06, 0x07];
+    let options = ParseOptions {
+      limits: ParseLimits {
+        max_actions: Some(1),
+        ..ParseLimits::default()
+      },
+      ..ParseOptions::default()
+    };
+    let actual = find_dead_code(&input, &options, &parse_cfg_report(&input, &options));
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].range, 1..3);
+    assert!(actual[0].unexplored);
+  }
+}
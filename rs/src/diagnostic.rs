@@ -0,0 +1,104 @@
+use crate::error::ParseError;
+
+/// Non-fatal issue found while parsing.
+///
+/// Diagnostics describe input that was accepted but is suspicious or was only partially decoded.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Diagnostic {
+  /// A `Push` action was only decoded up to its first invalid value (see
+  /// `ParseOptions::recover_push`).
+  PartialPush {
+    /// Error that stopped the decoding of the values
+    error: ParseError,
+    /// Number of values successfully decoded
+    values: usize,
+    /// Undecodable bytes at the end of the action body, starting at the invalid value
+    tail: Vec<u8>,
+  },
+  /// The length declared in the header of the action does not match the length of its fields.
+  LengthMismatch {
+    /// Offset of the action
+    offset: usize,
+    /// Action code
+    code: u8,
+    /// Body length declared in the action header
+    declared: usize,
+    /// Number of body bytes used by the fields of the action.
+    ///
+    /// If the body ends before the last field, this is the number of bytes used by the fields
+    /// preceding the first incomplete field.
+    consumed: usize,
+    /// Minimum number of bytes missing at the end of the body to read the first incomplete field,
+    /// `0` if the fields fit in the body.
+    ///
+    /// This is exact for fixed-size fields; strings need at least their nul terminator.
+    needed: usize,
+  },
+  /// The code blocks of the action (function, `try` or `with` bodies) are larger than the
+  /// available input (see `ParseOptions::oversized_bodies`).
+  OversizedBody {
+    /// Offset of the action
+    offset: usize,
+    /// Action code
+    code: u8,
+    /// Total size of the code blocks, as declared by the action
+    declared: usize,
+    /// Number of bytes available after the action, up to the end of the enclosing block
+    available: usize,
+  },
+  /// The skip count of a `WaitForFrame` or `WaitForFrame2` action runs past the end of the
+  /// input: its loading target is the end of the code.
+  SkipOutOfBounds {
+    /// Offset of the action
+    offset: usize,
+    /// Action code
+    code: u8,
+    /// Number of actions to skip, as declared by the action
+    skip: usize,
+    /// Number of complete actions available after the action
+    skipped: usize,
+  },
+  /// The action is not recognized by the player for the SWF version of the input (see
+  /// `ParseOptions::check_versions`).
+  UnsupportedAction {
+    /// Offset of the action
+    offset: usize,
+    /// Action code
+    code: u8,
+    /// SWF version of the input
+    swf_version: u8,
+    /// First SWF version recognizing the action, `None` if no version recognizes it
+    min_version: Option<u8>,
+    /// How the player handles the action
+    behavior: UnknownActionBehavior,
+  },
+}
+
+/// Handling of an unrecognized action by the player
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnknownActionBehavior {
+  /// The action is skipped, execution continues with the next action
+  Skip,
+  /// The action is skipped, but not the code blocks it holds (function, `try` or `with` bodies):
+  /// execution continues with the first of these blocks.
+  InlineBody,
+}
+
+impl Diagnostic {
+  /// Offset of the action causing this diagnostic
+  pub fn offset(&self) -> usize {
+    match self {
+      Diagnostic::PartialPush { error, .. } => error.offset,
+      Diagnostic::LengthMismatch { offset, .. } => *offset,
+      Diagnostic::OversizedBody { offset, .. } => *offset,
+      Diagnostic::SkipOutOfBounds { offset, .. } => *offset,
+      Diagnostic::UnsupportedAction { offset, .. } => *offset,
+    }
+  }
+}
+
+/// Sorts diagnostics by offset and removes duplicates (when an action is decoded multiple times).
+pub(crate) fn normalize_diagnostics(diagnostics: &mut Vec<Diagnostic>) {
+  diagnostics.sort_by(|left, right| left.offset().cmp(&right.offset()).then_with(|| left.cmp(right)));
+  diagnostics.dedup();
+}
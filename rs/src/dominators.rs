@@ -0,0 +1,318 @@
+use crate::graph::FlowGraph;
+use avm1_types::cfg::CfgLabel;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Dominator tree (or post-dominator tree) of a `FlowGraph`.
+///
+/// The root of a dominator tree is the entry block. The root of a post-dominator tree is the
+/// implicit exit node: blocks immediately post-dominated by the exit have no parent in the tree.
+/// Blocks that are not reachable from the root (unreachable blocks, or infinite loops for the
+/// post-dominators) are not part of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DominatorTree {
+  labels: Vec<CfgLabel>,
+  indexes: HashMap<CfgLabel, usize>,
+  /// Immediate dominator of each node, the root is its own dominator
+  idoms: Vec<Option<usize>>,
+  root: usize,
+}
+
+impl DominatorTree {
+  fn new(graph: &FlowGraph, idoms: Vec<Option<usize>>, root: usize) -> Self {
+    let labels = graph.labels().to_vec();
+    let indexes = labels.iter().cloned().enumerate().map(|(i, l)| (l, i)).collect();
+    Self {
+      labels,
+      indexes,
+      idoms,
+      root,
+    }
+  }
+
+  /// Returns `true` if `label` is part of the tree.
+  pub fn contains(&self, label: &CfgLabel) -> bool {
+    self.node(label).is_some()
+  }
+
+  /// Returns the immediate dominator of `label`, `None` for the root of the tree, for blocks
+  /// outside of the tree and for blocks immediately post-dominated by the exit.
+  pub fn immediate_dominator(&self, label: &CfgLabel) -> Option<&CfgLabel> {
+    let node = self.node(label)?;
+    let idom = self.idoms[node]?;
+    if idom == node || idom == self.labels.len() {
+      None
+    } else {
+      Some(&self.labels[idom])
+    }
+  }
+
+  /// Returns `true` if `dominator` dominates `label` (a block dominates itself).
+  pub fn dominates(&self, dominator: &CfgLabel, label: &CfgLabel) -> bool {
+    match (self.node(dominator), self.node(label)) {
+      (Some(dominator), Some(node)) => self.dominates_node(dominator, node),
+      _ => false,
+    }
+  }
+
+  /// Returns the blocks immediately dominated by `label`, in label order.
+  pub fn children(&self, label: &CfgLabel) -> Vec<&CfgLabel> {
+    let node = match self.node(label) {
+      Some(node) => node,
+      None => return Vec::new(),
+    };
+    let mut children: Vec<&CfgLabel> = (0..self.labels.len())
+      .filter(|child| *child != node && self.idoms[*child] == Some(node))
+      .map(|child| &self.labels[child])
+      .collect();
+    children.sort();
+    children
+  }
+
+  /// Returns the immediate dominator of each block of the tree, except the root.
+  pub fn immediate_dominators(&self) -> BTreeMap<&CfgLabel, Option<&CfgLabel>> {
+    self
+      .labels
+      .iter()
+      .filter(|label| self.contains(label) && self.node(label) != Some(self.root))
+      .map(|label| (label, self.immediate_dominator(label)))
+      .collect()
+  }
+
+  fn node(&self, label: &CfgLabel) -> Option<usize> {
+    let node = *self.indexes.get(label)?;
+    self.idoms[node].map(|_| node)
+  }
+
+  fn dominates_node(&self, dominator: usize, mut node: usize) -> bool {
+    loop {
+      if node == dominator {
+        return true;
+      }
+      match self.idoms.get(node).copied().flatten() {
+        Some(idom) if idom != node => node = idom,
+        _ => return false,
+      }
+    }
+  }
+}
+
+/// Computes the dominator tree of `graph`, rooted at its entry block.
+pub fn dominators(graph: &FlowGraph) -> DominatorTree {
+  let successors = |node: usize| graph.successor_indexes(node).to_vec();
+  let predecessors = |node: usize| graph.predecessor_indexes(node).to_vec();
+  let idoms = compute_idoms(graph.len(), 0, successors, predecessors);
+  DominatorTree::new(graph, idoms, 0)
+}
+
+/// Computes the post-dominator tree of `graph`, rooted at its implicit exit node.
+pub fn post_dominators(graph: &FlowGraph) -> DominatorTree {
+  // Reverse graph, with the exit node at index `graph.len()`
+  let exit = graph.len();
+  let successors = |node: usize| {
+    if node == exit {
+      graph.exit_indexes().to_vec()
+    } else {
+      graph.predecessor_indexes(node).to_vec()
+    }
+  };
+  let predecessors = |node: usize| {
+    let mut predecessors = graph.successor_indexes(node).to_vec();
+    if graph.exit_indexes().contains(&node) {
+      predecessors.push(exit);
+    }
+    predecessors
+  };
+  let mut idoms = compute_idoms(graph.len() + 1, exit, successors, predecessors);
+  idoms.truncate(graph.len());
+  DominatorTree::new(graph, idoms, exit)
+}
+
+/// Computes the dominance frontier of each block of `tree`.
+///
+/// Use the tree returned by `post_dominators` to compute the post-dominance frontiers (control
+/// dependences): the frontiers are then computed over the reverse graph.
+pub fn dominance_frontiers(graph: &FlowGraph, tree: &DominatorTree) -> BTreeMap<CfgLabel, BTreeSet<CfgLabel>> {
+  let post = tree.root == graph.len();
+  let mut frontiers: BTreeMap<CfgLabel, BTreeSet<CfgLabel>> = BTreeMap::new();
+  for node in 0..graph.len() {
+    let idom = match tree.idoms[node] {
+      Some(idom) => idom,
+      None => continue,
+    };
+    frontiers.entry(graph.label(node).clone()).or_default();
+    let mut predecessors: Vec<usize> = if post {
+      graph.successor_indexes(node).to_vec()
+    } else {
+      graph.predecessor_indexes(node).to_vec()
+    };
+    if post && graph.exit_indexes().contains(&node) {
+      predecessors.push(tree.root);
+    }
+    // The entry block has an implicit predecessor (the entry edge), dominated by none of the
+    // blocks: the walks from its other predecessors reach the entry block itself.
+    let entry = !post && node == tree.root;
+    if predecessors.len() + usize::from(entry) < 2 {
+      continue;
+    }
+    for predecessor in predecessors {
+      let mut runner = predecessor;
+      while entry || (runner != idom && runner != tree.root) {
+        if tree.idoms[runner].is_none() {
+          break;
+        }
+        frontiers
+          .entry(graph.label(runner).clone())
+          .or_default()
+          .insert(graph.label(node).clone());
+        if runner == tree.root {
+          break;
+        }
+        runner = tree.idoms[runner].unwrap_or(runner);
+      }
+    }
+  }
+  frontiers
+}
+
+/// Computes the immediate dominators with the iterative algorithm of Cooper, Harvey and Kennedy.
+///
+/// Returns the immediate dominator of each node, `None` for nodes unreachable from `root`.
+fn compute_idoms(
+  len: usize,
+  root: usize,
+  successors: impl Fn(usize) -> Vec<usize>,
+  predecessors: impl Fn(usize) -> Vec<usize>,
+) -> Vec<Option<usize>> {
+  let rpo = reverse_postorder(len, root, &successors);
+  let mut order: Vec<usize> = vec![usize::MAX; len];
+  for (position, node) in rpo.iter().enumerate() {
+    order[*node] = position;
+  }
+
+  let mut idoms: Vec<Option<usize>> = vec![None; len];
+  idoms[root] = Some(root);
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for node in rpo.iter().skip(1).copied() {
+      let mut new_idom: Option<usize> = None;
+      for predecessor in predecessors(node) {
+        if idoms[predecessor].is_none() {
+          continue;
+        }
+        new_idom = Some(match new_idom {
+          None => predecessor,
+          Some(other) => intersect(&idoms, &order, predecessor, other),
+        });
+      }
+      if new_idom.is_some() && idoms[node] != new_idom {
+        idoms[node] = new_idom;
+        changed = true;
+      }
+    }
+  }
+  idoms
+}
+
+fn intersect(idoms: &[Option<usize>], order: &[usize], mut left: usize, mut right: usize) -> usize {
+  while left != right {
+    while order[left] > order[right] {
+      left = idoms[left].expect("processed nodes have a dominator");
+    }
+    while order[right] > order[left] {
+      right = idoms[right].expect("processed nodes have a dominator");
+    }
+  }
+  left
+}
+
+/// Returns the nodes reachable from `root`, in reverse post-order.
+fn reverse_postorder(len: usize, root: usize, successors: impl Fn(usize) -> Vec<usize>) -> Vec<usize> {
+  let mut visited: Vec<bool> = vec![false; len];
+  let mut postorder: Vec<usize> = Vec::new();
+  // Explicit stack of (node, successors, next successor) to support deep graphs
+  let mut stack: Vec<(usize, Vec<usize>, usize)> = vec![(root, successors(root), 0)];
+  visited[root] = true;
+  while let Some((node, targets, next)) = stack.last_mut() {
+    if let Some(target) = targets.get(*next).copied() {
+      *next += 1;
+      if !visited[target] {
+        visited[target] = true;
+        let targets = successors(target);
+        stack.push((target, targets, 0));
+      }
+    } else {
+      postorder.push(*node);
+      stack.pop();
+    }
+  }
+  postorder.reverse();
+  postorder
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg;
+  use crate::test_util::label;
+
+  #[test]
+  fn test_dominators() {
+    // If(+1); Play; Stop
+    let input = [0x9d, 0x02, 0x00, 0x01, 0x00, 0x06, 0x07];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    assert_eq!(graph.labels(), [label("l0_0"), label("l0_5"), label("l0_6")]);
+
+    let tree = dominators(&graph);
+    assert_eq!(tree.immediate_dominator(&label("l0_0")), None);
+    assert_eq!(tree.immediate_dominator(&label("l0_5")), Some(&label("l0_0")));
+    assert_eq!(tree.immediate_dominator(&label("l0_6")), Some(&label("l0_0")));
+    assert!(!tree.dominates(&label("l0_5"), &label("l0_6")));
+    assert_eq!(tree.children(&label("l0_0")), [&label("l0_5"), &label("l0_6")]);
+
+    let frontiers = dominance_frontiers(&graph, &tree);
+    assert_eq!(frontiers[&label("l0_0")], BTreeSet::new());
+    assert_eq!(frontiers[&label("l0_5")], BTreeSet::from([label("l0_6")]));
+
+    let post_tree = post_dominators(&graph);
+    assert_eq!(post_tree.immediate_dominator(&label("l0_0")), Some(&label("l0_6")));
+    assert_eq!(post_tree.immediate_dominator(&label("l0_5")), Some(&label("l0_6")));
+    assert_eq!(post_tree.immediate_dominator(&label("l0_6")), None);
+    assert!(post_tree.dominates(&label("l0_6"), &label("l0_0")));
+
+    let post_frontiers = dominance_frontiers(&graph, &post_tree);
+    assert_eq!(post_frontiers[&label("l0_5")], BTreeSet::from([label("l0_0")]));
+    assert_eq!(post_frontiers[&label("l0_6")], BTreeSet::new());
+  }
+
+  #[test]
+  fn test_dominance_frontiers_entry_loop() {
+    // Play; Jump(-6)
+    let input = [0x06, 0x99, 0x02, 0x00, 0xfa, 0xff];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    let frontiers = dominance_frontiers(&graph, &dominators(&graph));
+    assert_eq!(frontiers[&label("l0_0")], BTreeSet::from([label("l0_0")]));
+
+    // Play; If(-6); Stop
+    let input = [0x06, 0x9d, 0x02, 0x00, 0xfa, 0xff, 0x07];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    assert_eq!(graph.labels(), [label("l0_0"), label("l0_6")]);
+    let frontiers = dominance_frontiers(&graph, &dominators(&graph));
+    assert_eq!(frontiers[&label("l0_0")], BTreeSet::from([label("l0_0")]));
+    assert_eq!(frontiers[&label("l0_6")], BTreeSet::new());
+  }
+
+  #[test]
+  fn test_dominators_nested() {
+    // With { Play }; Stop
+    let input = [0x94, 0x02, 0x00, 0x01, 0x00, 0x06, 0x07];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    assert_eq!(graph.labels(), [label("l0_0"), label("l1_5"), label("l0_6")]);
+
+    let tree = dominators(&graph);
+    assert_eq!(tree.immediate_dominator(&label("l1_5")), Some(&label("l0_0")));
+    assert_eq!(tree.immediate_dominator(&label("l0_6")), Some(&label("l1_5")));
+    let post_tree = post_dominators(&graph);
+    assert_eq!(post_tree.immediate_dominator(&label("l0_0")), Some(&label("l1_5")));
+  }
+}
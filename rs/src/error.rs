@@ -0,0 +1,156 @@
+use avm1_types::error::InvalidActionError;
+use avm1_types::raw;
+use core::fmt;
+use nom::error::{ContextError, ErrorKind};
+use nom::InputLength;
+
+/// Error describing why the body of an action could not be decoded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParseError {
+  /// Offset of the action (start of its header) in the input
+  pub offset: usize,
+  /// Action code
+  pub code: u8,
+  /// Name of the field being read when the error occurred.
+  ///
+  /// Fields are named after the field of the `raw` action they decode to, nested fields use a
+  /// dotted path (`catch.target`, `catch.size`). The length prefix of a list is named after the
+  /// list with a `.len` suffix (`pool.len`, `parameters.len`). Encoded fields without a `raw`
+  /// counterpart keep their SWF name (`flags`). Errors outside of the body use `length` (action
+  /// header), `body` (action body) or `action` (whole action, for resource limits).
+  pub field: &'static str,
+  /// Machine-readable error kind
+  pub kind: ParseErrorKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ParseErrorKind {
+  /// The action ended before the field could be fully read
+  UnexpectedEnd,
+  /// A string is missing its nul terminator
+  UnterminatedString,
+  /// A string is not valid for the expected encoding
+  InvalidString,
+  /// Unknown value type code in a `Push` action
+  UnknownPushValueType(u8),
+  /// Unknown method code in a `GetUrl2` action
+  UnknownGetUrl2Method(u8),
+  /// Bytes left in the action body after its last field (strict mode only)
+  TrailingBytes(usize),
+  /// The action was not decoded because `ParseLimits::max_actions` was exhausted
+  ActionLimitExceeded,
+  /// The body of the action was not parsed because it exceeds `ParseLimits::max_depth`
+  NestingLimitExceeded,
+  /// The code blocks of the action are larger than the available input
+  /// (`OversizedBodyPolicy::Error` only)
+  OversizedBody { declared: usize, available: usize },
+}
+
+impl fmt::Display for ParseErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ParseErrorKind::UnexpectedEnd => f.write_str("unexpected end of action"),
+      ParseErrorKind::UnterminatedString => f.write_str("unterminated string"),
+      ParseErrorKind::InvalidString => f.write_str("invalid string"),
+      ParseErrorKind::UnknownPushValueType(code) => write!(f, "unknown push value type {}", code),
+      ParseErrorKind::UnknownGetUrl2Method(code) => write!(f, "unknown GetUrl2 method {}", code),
+      ParseErrorKind::TrailingBytes(len) => write!(f, "{} trailing bytes", len),
+      ParseErrorKind::ActionLimitExceeded => f.write_str("action limit exceeded"),
+      ParseErrorKind::NestingLimitExceeded => f.write_str("nesting limit exceeded"),
+      ParseErrorKind::OversizedBody { declared, available } => {
+        write!(f, "body size {} exceeds the {} available bytes", declared, available)
+      }
+    }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} in field `{}` of action 0x{:02x} at offset {}",
+      self.kind, self.field, self.code, self.offset
+    )
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for InvalidActionError {
+  fn from(error: ParseError) -> Self {
+    InvalidActionError {
+      message: error.to_string(),
+    }
+  }
+}
+
+impl From<ParseError> for raw::Error {
+  fn from(error: ParseError) -> Self {
+    raw::Error {
+      error: Some(error.into()),
+    }
+  }
+}
+
+/// Nom error type used by the action body parsers.
+///
+/// The action offset and code are not known while parsing the body, they are
+/// attached with `BodyError::at` once the body parser returns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BodyError {
+  /// Innermost field reported through `nom::error::context`
+  pub(crate) field: Option<&'static str>,
+  pub(crate) kind: ParseErrorKind,
+  /// Length of the input left at the start of the incomplete field
+  pub(crate) remaining: usize,
+  /// Minimum number of bytes missing to read the incomplete field
+  pub(crate) needed: usize,
+}
+
+impl BodyError {
+  pub(crate) fn new(kind: ParseErrorKind) -> Self {
+    Self::missing(kind, 0, 0)
+  }
+
+  /// Returns the error for a field starting with `remaining` bytes left in the input, missing at
+  /// least `needed` bytes.
+  pub(crate) fn missing(kind: ParseErrorKind, remaining: usize, needed: usize) -> Self {
+    Self {
+      field: None,
+      kind,
+      remaining,
+      needed,
+    }
+  }
+
+  pub(crate) fn at(self, offset: usize, code: u8) -> ParseError {
+    ParseError {
+      offset,
+      code,
+      field: self.field.unwrap_or("body"),
+      kind: self.kind,
+    }
+  }
+}
+
+impl<I: InputLength> nom::error::ParseError<I> for BodyError {
+  fn from_error_kind(input: I, _kind: ErrorKind) -> Self {
+    // Action bodies are parsed with `complete` parsers (missing string
+    // terminators are reported by `parse_c_string`): nom only fails when
+    // running out of input.
+    Self::missing(ParseErrorKind::UnexpectedEnd, input.input_len(), 1)
+  }
+
+  fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+    other
+  }
+}
+
+impl<I> ContextError<I> for BodyError {
+  fn add_context(_input: I, ctx: &'static str, mut other: Self) -> Self {
+    if other.field.is_none() {
+      other.field = Some(ctx);
+    }
+    other
+  }
+}
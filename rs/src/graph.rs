@@ -0,0 +1,188 @@
+use avm1_types::cfg;
+use avm1_types::cfg::{Cfg, CfgFlow, CfgLabel};
+use std::collections::HashMap;
+
+/// Control flow graph of the blocks of a function (or of the root code).
+///
+/// The graph contains the blocks of the CFG and of its nested `try`, `catch`, `finally` and
+/// `with` bodies: jumps out of these bodies target blocks of the enclosing CFGs. The bodies of
+/// nested functions are separate graphs (see `function_bodies`).
+///
+/// Edges follow the `CfgFlow` of each block:
+/// - `Try` blocks have an edge to the entry of the `try` body, and to the entry of the `catch`
+///   and `finally` bodies (an exception may be thrown before the first action of the `try` body).
+/// - `With` blocks have an edge to the entry of their body.
+/// - Blocks ending the function (`Return`, `Throw`, `Error` and jumps to the end of the code) have
+///   an edge to the implicit exit node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlowGraph {
+  labels: Vec<CfgLabel>,
+  indexes: HashMap<CfgLabel, usize>,
+  successors: Vec<Vec<usize>>,
+  predecessors: Vec<Vec<usize>>,
+  /// Nodes with an edge to the exit node
+  exits: Vec<usize>,
+}
+
+impl FlowGraph {
+  pub fn new(cfg: &Cfg) -> Self {
+    let mut blocks: Vec<&cfg::CfgBlock> = Vec::new();
+    collect_blocks(cfg, &mut blocks);
+    let labels: Vec<CfgLabel> = blocks.iter().map(|block| block.label.clone()).collect();
+    let indexes: HashMap<CfgLabel, usize> = labels.iter().cloned().enumerate().map(|(i, l)| (l, i)).collect();
+
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); blocks.len()];
+    let mut exits: Vec<usize> = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+      let mut targets: Vec<Option<&CfgLabel>> = Vec::new();
+      match &block.flow {
+        CfgFlow::Error(_) | CfgFlow::Return | CfgFlow::Throw => targets.push(None),
+        CfgFlow::If(flow) => {
+          targets.push(flow.true_target.as_ref());
+          targets.push(flow.false_target.as_ref());
+        }
+        CfgFlow::Simple(flow) => targets.push(flow.next.as_ref()),
+        CfgFlow::Try(flow) => {
+          targets.push(Some(entry_label(&flow.r#try)));
+          if let Some(catch) = flow.catch.as_ref() {
+            targets.push(Some(entry_label(&catch.body)));
+          }
+          if let Some(finally) = flow.finally.as_ref() {
+            targets.push(Some(entry_label(finally)));
+          }
+        }
+        CfgFlow::WaitForFrame(flow) => {
+          targets.push(flow.ready_target.as_ref());
+          targets.push(flow.loading_target.as_ref());
+        }
+        CfgFlow::WaitForFrame2(flow) => {
+          targets.push(flow.ready_target.as_ref());
+          targets.push(flow.loading_target.as_ref());
+        }
+        CfgFlow::With(flow) => targets.push(Some(entry_label(&flow.body))),
+      }
+      for target in targets {
+        // Jumps to labels outside of the graph leave the function.
+        match target.and_then(|label| indexes.get(label)) {
+          Some(target) if !successors[index].contains(target) => successors[index].push(*target),
+          Some(_) => {}
+          None if !exits.contains(&index) => exits.push(index),
+          None => {}
+        }
+      }
+    }
+
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); blocks.len()];
+    for (index, targets) in successors.iter().enumerate() {
+      for target in targets {
+        predecessors[*target].push(index);
+      }
+    }
+
+    Self {
+      labels,
+      indexes,
+      successors,
+      predecessors,
+      exits,
+    }
+  }
+
+  /// Labels of the blocks, the entry block first
+  pub fn labels(&self) -> &[CfgLabel] {
+    &self.labels
+  }
+
+  pub fn entry(&self) -> &CfgLabel {
+    &self.labels[0]
+  }
+
+  pub fn contains(&self, label: &CfgLabel) -> bool {
+    self.indexes.contains_key(label)
+  }
+
+  pub fn successors(&self, label: &CfgLabel) -> impl Iterator<Item = &CfgLabel> + '_ {
+    self.neighbors(&self.successors, label)
+  }
+
+  pub fn predecessors(&self, label: &CfgLabel) -> impl Iterator<Item = &CfgLabel> + '_ {
+    self.neighbors(&self.predecessors, label)
+  }
+
+  /// Returns `true` if the block has an edge to the exit node.
+  pub fn is_exit(&self, label: &CfgLabel) -> bool {
+    self.index(label).map_or(false, |index| self.exits.contains(&index))
+  }
+
+  fn neighbors<'g>(&'g self, edges: &'g [Vec<usize>], label: &CfgLabel) -> impl Iterator<Item = &'g CfgLabel> + 'g {
+    let nodes: &[usize] = match self.index(label) {
+      Some(index) => &edges[index],
+      None => &[],
+    };
+    nodes.iter().map(move |node| &self.labels[*node])
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.labels.len()
+  }
+
+  pub(crate) fn index(&self, label: &CfgLabel) -> Option<usize> {
+    self.indexes.get(label).copied()
+  }
+
+  pub(crate) fn label(&self, index: usize) -> &CfgLabel {
+    &self.labels[index]
+  }
+
+  pub(crate) fn successor_indexes(&self, index: usize) -> &[usize] {
+    &self.successors[index]
+  }
+
+  pub(crate) fn predecessor_indexes(&self, index: usize) -> &[usize] {
+    &self.predecessors[index]
+  }
+
+  pub(crate) fn exit_indexes(&self) -> &[usize] {
+    &self.exits
+  }
+}
+
+fn entry_label(cfg: &Cfg) -> &CfgLabel {
+  &cfg.blocks.first().label
+}
+
+/// Collects the blocks of `cfg` and of its nested bodies, except function bodies.
+pub(crate) fn collect_blocks<'a>(cfg: &'a Cfg, blocks: &mut Vec<&'a cfg::CfgBlock>) {
+  for block in cfg.blocks.iter() {
+    blocks.push(block);
+    match &block.flow {
+      CfgFlow::Try(flow) => {
+        collect_blocks(&flow.r#try, blocks);
+        if let Some(catch) = flow.catch.as_ref() {
+          collect_blocks(&catch.body, blocks);
+        }
+        if let Some(finally) = flow.finally.as_ref() {
+          collect_blocks(finally, blocks);
+        }
+      }
+      CfgFlow::With(flow) => collect_blocks(&flow.body, blocks),
+      _ => {}
+    }
+  }
+}
+
+/// Returns the bodies of the functions defined in `cfg` (including in its `try`, `catch`,
+/// `finally` and `with` bodies), without the functions nested in these functions.
+pub fn function_bodies(cfg: &Cfg) -> Vec<&Cfg> {
+  let mut blocks: Vec<&cfg::CfgBlock> = Vec::new();
+  collect_blocks(cfg, &mut blocks);
+  blocks
+    .iter()
+    .flat_map(|block| block.actions.iter())
+    .filter_map(|action| match action {
+      cfg::Action::DefineFunction(action) => Some(&action.body),
+      cfg::Action::DefineFunction2(action) => Some(&action.body),
+      _ => None,
+    })
+    .collect()
+}
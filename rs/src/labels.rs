@@ -0,0 +1,272 @@
+use avm1_types::cfg;
+use avm1_types::cfg::{Cfg, CfgFlow, CfgLabel};
+use std::collections::{BTreeMap, HashSet};
+
+/// Built-in naming schemes for the labels of the CFG blocks
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LabelStyle {
+  /// `l{layer}_{offset}`, where layers are numbered in traversal order (default)
+  Layer,
+  /// `l{offset}`
+  Offset,
+  /// Path of the block through the nested CFGs, followed by its offset: `f0/try1/l{offset}`
+  /// for a block in the `try` body of the second `Try` action of the first function.
+  Hierarchical,
+  /// `b{index}`, where blocks are numbered in depth-first order
+  Sequential,
+}
+
+impl Default for LabelStyle {
+  fn default() -> Self {
+    LabelStyle::Layer
+  }
+}
+
+/// Position of a nested CFG in its parent CFG.
+///
+/// Indexes count the nested CFGs of the same kind in the parent CFG, in block order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+  Function(usize),
+  Try(usize),
+  Catch(usize),
+  Finally(usize),
+  With(usize),
+}
+
+/// Block to name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelContext<'a> {
+  /// Label assigned by the parser (`l{layer}_{offset}`)
+  pub label: &'a CfgLabel,
+  /// Layer id of the block, if `label` is a parser label
+  pub layer: Option<u64>,
+  /// Offset of the first action of the block, if `label` is a parser label
+  pub offset: Option<usize>,
+  /// Path from the root CFG to the CFG containing the block
+  pub path: &'a [PathSegment],
+  /// Index of the block, in depth-first order
+  pub index: usize,
+}
+
+/// Labelling strategy
+pub trait LabelNamer {
+  /// Returns the name of the block. Duplicate names are made unique with a `_{n}` suffix.
+  fn name(&mut self, block: &LabelContext) -> String;
+}
+
+impl LabelNamer for LabelStyle {
+  fn name(&mut self, block: &LabelContext) -> String {
+    match (*self, block.offset) {
+      (LabelStyle::Offset, Some(offset)) => format!("l{}", offset),
+      (LabelStyle::Hierarchical, Some(offset)) => {
+        let mut name = String::new();
+        for segment in block.path {
+          let segment = match segment {
+            PathSegment::Function(i) => format!("f{}/", i),
+            PathSegment::Try(i) => format!("try{}/", i),
+            PathSegment::Catch(i) => format!("catch{}/", i),
+            PathSegment::Finally(i) => format!("finally{}/", i),
+            PathSegment::With(i) => format!("with{}/", i),
+          };
+          name.push_str(&segment);
+        }
+        name.push_str(&format!("l{}", offset));
+        name
+      }
+      (LabelStyle::Sequential, _) => format!("b{}", block.index),
+      _ => block.label.0.clone(),
+    }
+  }
+}
+
+/// Renames the labels of `cfg` (including nested CFGs) and updates the jump targets.
+///
+/// Returns the new label of each renamed block.
+pub fn rename_labels<N: LabelNamer + ?Sized>(cfg: &mut Cfg, namer: &mut N) -> BTreeMap<CfgLabel, CfgLabel> {
+  let mut renamer = Renamer {
+    namer,
+    path: Vec::new(),
+    index: 0,
+    used: HashSet::new(),
+    names: BTreeMap::new(),
+  };
+  renamer.name_cfg(cfg);
+  let names = renamer.names;
+  rewrite_cfg(cfg, &names);
+  names
+}
+
+struct Renamer<'n, N: LabelNamer + ?Sized> {
+  namer: &'n mut N,
+  path: Vec<PathSegment>,
+  index: usize,
+  used: HashSet<String>,
+  names: BTreeMap<CfgLabel, CfgLabel>,
+}
+
+impl<N: LabelNamer + ?Sized> Renamer<'_, N> {
+  fn name_cfg(&mut self, cfg: &Cfg) {
+    let mut functions: usize = 0;
+    let mut tries: usize = 0;
+    let mut withs: usize = 0;
+    for block in cfg.blocks.iter() {
+      self.name_block(&block.label);
+      for action in block.actions.iter() {
+        let body = match action {
+          cfg::Action::DefineFunction(action) => &action.body,
+          cfg::Action::DefineFunction2(action) => &action.body,
+          _ => continue,
+        };
+        self.name_nested(PathSegment::Function(functions), body);
+        functions += 1;
+      }
+      match &block.flow {
+        CfgFlow::Try(action) => {
+          self.name_nested(PathSegment::Try(tries), &action.r#try);
+          if let Some(catch) = action.catch.as_ref() {
+            self.name_nested(PathSegment::Catch(tries), &catch.body);
+          }
+          if let Some(finally) = action.finally.as_ref() {
+            self.name_nested(PathSegment::Finally(tries), finally);
+          }
+          tries += 1;
+        }
+        CfgFlow::With(action) => {
+          self.name_nested(PathSegment::With(withs), &action.body);
+          withs += 1;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn name_nested(&mut self, segment: PathSegment, cfg: &Cfg) {
+    self.path.push(segment);
+    self.name_cfg(cfg);
+    self.path.pop();
+  }
+
+  fn name_block(&mut self, label: &CfgLabel) {
+    let (layer, offset) = match parse_layer_label(label) {
+      Some((layer, offset)) => (Some(layer), Some(offset)),
+      None => (None, None),
+    };
+    let block = LabelContext {
+      label,
+      layer,
+      offset,
+      path: &self.path,
+      index: self.index,
+    };
+    let name = self.namer.name(&block);
+    self.index += 1;
+    let mut unique = name.clone();
+    let mut suffix: usize = 1;
+    while self.used.contains(&unique) {
+      unique = format!("{}_{}", name, suffix);
+      suffix += 1;
+    }
+    self.used.insert(unique.clone());
+    self.names.insert(label.clone(), CfgLabel(unique));
+  }
+}
+
+/// Parses a label produced by the parser (`l{layer}_{offset}`).
+pub(crate) fn parse_layer_label(label: &CfgLabel) -> Option<(u64, usize)> {
+  let (layer, offset) = label.0.strip_prefix('l')?.split_once('_')?;
+  Some((layer.parse().ok()?, offset.parse().ok()?))
+}
+
+fn rewrite_cfg(cfg: &mut Cfg, names: &BTreeMap<CfgLabel, CfgLabel>) {
+  let rename = |label: &mut Option<CfgLabel>| {
+    if let Some(new_label) = label.as_ref().and_then(|label| names.get(label)) {
+      *label = Some(new_label.clone());
+    }
+  };
+  for block in cfg.blocks.iter_mut() {
+    if let Some(new_label) = names.get(&block.label) {
+      block.label = new_label.clone();
+    }
+    for action in block.actions.iter_mut() {
+      match action {
+        cfg::Action::DefineFunction(action) => rewrite_cfg(&mut action.body, names),
+        cfg::Action::DefineFunction2(action) => rewrite_cfg(&mut action.body, names),
+        _ => {}
+      }
+    }
+    match &mut block.flow {
+      CfgFlow::If(flow) => {
+        rename(&mut flow.true_target);
+        rename(&mut flow.false_target);
+      }
+      CfgFlow::Simple(flow) => rename(&mut flow.next),
+      CfgFlow::Try(flow) => {
+        rewrite_cfg(&mut flow.r#try, names);
+        if let Some(catch) = flow.catch.as_mut() {
+          rewrite_cfg(&mut catch.body, names);
+        }
+        if let Some(finally) = flow.finally.as_mut() {
+          rewrite_cfg(finally, names);
+        }
+      }
+      CfgFlow::WaitForFrame(flow) => {
+        rename(&mut flow.loading_target);
+        rename(&mut flow.ready_target);
+      }
+      CfgFlow::WaitForFrame2(flow) => {
+        rename(&mut flow.loading_target);
+        rename(&mut flow.ready_target);
+      }
+      CfgFlow::With(flow) => rewrite_cfg(&mut flow.body, names),
+      CfgFlow::Error(_) | CfgFlow::Return | CfgFlow::Throw => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg_report;
+  use crate::options::ParseOptions;
+
+  fn labels(cfg: &Cfg) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for block in cfg.blocks.iter() {
+      result.push(block.label.0.clone());
+      if let CfgFlow::With(with) = &block.flow {
+        result.extend(labels(&with.body));
+      }
+    }
+    result
+  }
+
+  #[test]
+  fn test_label_styles() {
+    // Jump(+1); Stop; With { Play }
+    let input = [0x99, 0x02, 0x00, 0x01, 0x00, 0x07, 0x94, 0x02, 0x00, 0x01, 0x00, 0x06];
+    let expected = [
+      (LabelStyle::Layer, ["l0_0", "l0_6", "l1_11"]),
+      (LabelStyle::Offset, ["l0", "l6", "l11"]),
+      (LabelStyle::Hierarchical, ["l0", "l6", "with0/l11"]),
+      (LabelStyle::Sequential, ["b0", "b1", "b2"]),
+    ];
+    for (style, expected) in expected.iter() {
+      let options = ParseOptions {
+        labels: *style,
+        ..ParseOptions::default()
+      };
+      let report = parse_cfg_report(&input, &options);
+      assert_eq!(labels(&report.cfg), expected);
+      let next = CfgLabel(String::from(expected[1]));
+      assert_eq!(
+        report.cfg.blocks[0].flow,
+        CfgFlow::Simple(cfg::Simple { next: Some(next) })
+      );
+      let mut sorted: Vec<&str> = expected.to_vec();
+      sorted.sort_unstable();
+      let source_map_labels: Vec<&str> = report.source_map.blocks.keys().map(|label| label.0.as_str()).collect();
+      assert_eq!(source_map_labels, sorted);
+    }
+  }
+}
@@ -1,9 +1,50 @@
-pub use crate::avm1::parse_action;
-pub use crate::cfg::parse_cfg;
+pub use crate::avm1::{parse_action, parse_action_detailed, parse_action_lossless, parse_action_with, ActionTrivia};
+pub use crate::cfg::{
+  build_cfg, build_cfg_report, parse_cfg, parse_cfg_lazy, parse_cfg_report, parse_cfg_with, CfgReport, FunctionHandle,
+  LazyCfg,
+};
+pub use crate::constants::{resolve_constants, ConstantRef, ConstantResolution};
+pub use crate::convert::{
+  action_code, action_successors, convert_action, ActionClass, ActionSuccessors, FromRawActionError,
+};
+pub use crate::dead_code::{find_dead_code, DeadCode};
+pub use crate::diagnostic::{Diagnostic, UnknownActionBehavior};
+pub use crate::dominators::{dominance_frontiers, dominators, post_dominators, DominatorTree};
+pub use crate::error::{ParseError, ParseErrorKind};
+pub use crate::graph::{function_bodies, FlowGraph};
+pub use crate::labels::{rename_labels, LabelContext, LabelNamer, LabelStyle, PathSegment};
+pub use crate::loops::{find_loops, Loop, LoopExit, LoopForest};
+pub use crate::options::{OversizedBodyPolicy, ParseLimits, ParseOptions, StringEncoding};
+pub use crate::reader::{ActionParts, ActionReader};
+pub use crate::registers::{
+  register_usage, Preload, RegisterAccess, RegisterAccessKind, RegisterLocation, RegisterUsage,
+};
+pub use crate::source_map::{ActionSpan, BlockSpan, Overlap, SourceMap};
+pub use crate::stack::{flow_stack_effect, raw_stack_effect, stack_effect, StackCount, StackEffect};
+pub use crate::verify::{verify_stack, RegionKind, StackIssue, StackReport};
+pub use crate::version::{action_swf_version, unknown_action_behavior};
 
 mod avm1;
 mod basic_data_types;
 mod cfg;
+mod constants;
+mod convert;
+mod dead_code;
+mod diagnostic;
+mod dominators;
+mod error;
+mod graph;
+mod labels;
+mod loops;
+mod options;
+mod reader;
+mod registers;
+mod source_map;
+mod stack;
+#[cfg(test)]
+mod test_util;
+mod verify;
+mod version;
 
 #[cfg(test)]
 mod parser_tests {
@@ -39,7 +80,7 @@ mod parser_tests {
     let path: &Path = Path::new(path);
     let _name = path
       .components()
-      .last()
+      .next_back()
       .unwrap()
       .as_os_str()
       .to_str()
@@ -0,0 +1,318 @@
+use crate::graph::FlowGraph;
+use avm1_types::cfg::CfgLabel;
+use std::collections::{BTreeSet, HashSet};
+
+/// Loop of a `FlowGraph`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loop {
+  /// Entry blocks of the loop, sorted.
+  ///
+  /// Natural loops have a single header, dominating the blocks of the loop. Irreducible loops
+  /// have multiple headers: the loop can be entered at any of them.
+  pub headers: Vec<CfgLabel>,
+  /// Blocks of the loop, including the blocks of the nested loops
+  pub blocks: BTreeSet<CfgLabel>,
+  /// Blocks of the loop jumping back to a header, sorted
+  pub latches: Vec<CfgLabel>,
+  /// Edges leaving the loop, sorted
+  pub exits: Vec<LoopExit>,
+  /// Index of the innermost enclosing loop in the forest
+  pub parent: Option<usize>,
+  /// Indexes of the loops directly nested in this loop
+  pub children: Vec<usize>,
+  /// Nesting depth, `0` for outermost loops
+  pub depth: usize,
+}
+
+impl Loop {
+  pub fn is_irreducible(&self) -> bool {
+    self.headers.len() > 1
+  }
+}
+
+/// Edge leaving a loop
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LoopExit {
+  /// Block of the loop
+  pub from: CfgLabel,
+  /// Block outside of the loop, `None` if the edge leaves the function
+  pub to: Option<CfgLabel>,
+}
+
+/// Loop nesting forest of a `FlowGraph`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoopForest {
+  /// Loops, parents before their children
+  pub loops: Vec<Loop>,
+}
+
+impl LoopForest {
+  /// Returns the indexes of the outermost loops.
+  pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+    (0..self.loops.len()).filter(move |index| self.loops[*index].parent.is_none())
+  }
+
+  /// Returns the index of the innermost loop containing `label`, if any.
+  pub fn innermost(&self, label: &CfgLabel) -> Option<usize> {
+    (0..self.loops.len())
+      .filter(|index| self.loops[*index].blocks.contains(label))
+      .max_by_key(|index| self.loops[*index].depth)
+  }
+
+  /// Returns the number of loops containing `label`.
+  pub fn loop_depth(&self, label: &CfgLabel) -> usize {
+    self.loops.iter().filter(|l| l.blocks.contains(label)).count()
+  }
+}
+
+/// Finds the loops of `graph` and builds their nesting forest.
+///
+/// Loops are the strongly connected components of the graph: the headers of a loop are its blocks
+/// with a predecessor outside of the loop (or the entry block). The nested loops are found by
+/// removing the edges to the headers and decomposing the loop again. For reducible graphs, this
+/// gives the natural loops (merging the loops sharing a header); irreducible loops are reported
+/// with all their headers instead of being rejected. Blocks unreachable from the entry block are
+/// ignored.
+pub fn find_loops(graph: &FlowGraph) -> LoopForest {
+  let reachable = reachable(graph);
+  let mut forest = LoopForest::default();
+  let mut removed: HashSet<(usize, usize)> = HashSet::new();
+  find_nested_loops(graph, &reachable, &reachable, &mut removed, None, &mut forest);
+  forest
+}
+
+fn find_nested_loops(
+  graph: &FlowGraph,
+  reachable: &[bool],
+  members: &[bool],
+  removed: &mut HashSet<(usize, usize)>,
+  parent: Option<usize>,
+  forest: &mut LoopForest,
+) {
+  let edges = |removed: &HashSet<(usize, usize)>, node: usize| -> Vec<usize> {
+    graph
+      .successor_indexes(node)
+      .iter()
+      .copied()
+      .filter(|target| members[*target] && !removed.contains(&(node, *target)))
+      .collect()
+  };
+
+  for component in strongly_connected_components(graph.len(), members, |node| edges(removed, node)) {
+    let is_loop = component.len() > 1 || edges(removed, component[0]).contains(&component[0]);
+    if !is_loop {
+      continue;
+    }
+    let mut in_component: Vec<bool> = vec![false; graph.len()];
+    for node in component.iter() {
+      in_component[*node] = true;
+    }
+
+    let mut headers: Vec<usize> = component
+      .iter()
+      .copied()
+      .filter(|node| {
+        *node == 0
+          || graph
+            .predecessor_indexes(*node)
+            .iter()
+            .any(|predecessor| reachable[*predecessor] && !in_component[*predecessor])
+      })
+      .collect();
+    if headers.is_empty() {
+      // Only reachable through removed edges of the enclosing loop
+      headers.push(component[0]);
+    }
+
+    let mut latches: Vec<usize> = Vec::new();
+    let mut exits: Vec<LoopExit> = Vec::new();
+    for node in component.iter().copied() {
+      for target in graph.successor_indexes(node).iter().copied() {
+        if !in_component[target] {
+          exits.push(LoopExit {
+            from: graph.label(node).clone(),
+            to: Some(graph.label(target).clone()),
+          });
+        } else if headers.contains(&target) && !removed.contains(&(node, target)) {
+          latches.push(node);
+          removed.insert((node, target));
+        }
+      }
+      if graph.exit_indexes().contains(&node) {
+        exits.push(LoopExit {
+          from: graph.label(node).clone(),
+          to: None,
+        });
+      }
+    }
+    latches.dedup();
+    exits.sort();
+
+    let index = forest.loops.len();
+    let depth = match parent {
+      Some(parent) => {
+        forest.loops[parent].children.push(index);
+        forest.loops[parent].depth + 1
+      }
+      None => 0,
+    };
+    forest.loops.push(Loop {
+      headers: sorted_labels(graph, &headers),
+      blocks: component.iter().map(|node| graph.label(*node).clone()).collect(),
+      latches: sorted_labels(graph, &latches),
+      exits,
+      parent,
+      children: Vec::new(),
+      depth,
+    });
+    find_nested_loops(graph, reachable, &in_component, removed, Some(index), forest);
+  }
+}
+
+fn sorted_labels(graph: &FlowGraph, nodes: &[usize]) -> Vec<CfgLabel> {
+  let mut labels: Vec<CfgLabel> = nodes.iter().map(|node| graph.label(*node).clone()).collect();
+  labels.sort();
+  labels
+}
+
+fn reachable(graph: &FlowGraph) -> Vec<bool> {
+  let mut visited: Vec<bool> = vec![false; graph.len()];
+  let mut stack: Vec<usize> = vec![0];
+  visited[0] = true;
+  while let Some(node) = stack.pop() {
+    for target in graph.successor_indexes(node).iter().copied() {
+      if !visited[target] {
+        visited[target] = true;
+        stack.push(target);
+      }
+    }
+  }
+  visited
+}
+
+/// Returns the strongly connected components of the subgraph induced by `members` (Kosaraju's
+/// algorithm), ordered by their first node. The nodes of each component are sorted.
+fn strongly_connected_components(
+  len: usize,
+  members: &[bool],
+  successors: impl Fn(usize) -> Vec<usize>,
+) -> Vec<Vec<usize>> {
+  // First pass: post-order of the graph
+  let mut visited: Vec<bool> = vec![false; len];
+  let mut postorder: Vec<usize> = Vec::new();
+  for root in (0..len).filter(|node| members[*node]) {
+    if visited[root] {
+      continue;
+    }
+    visited[root] = true;
+    let mut stack: Vec<(usize, Vec<usize>, usize)> = vec![(root, successors(root), 0)];
+    while let Some((node, targets, next)) = stack.last_mut() {
+      if let Some(target) = targets.get(*next).copied() {
+        *next += 1;
+        if !visited[target] {
+          visited[target] = true;
+          let targets = successors(target);
+          stack.push((target, targets, 0));
+        }
+      } else {
+        postorder.push(*node);
+        stack.pop();
+      }
+    }
+  }
+
+  // Second pass: traversal of the reverse graph in reverse post-order
+  let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); len];
+  for node in (0..len).filter(|node| members[*node]) {
+    for target in successors(node) {
+      predecessors[target].push(node);
+    }
+  }
+  let mut assigned: Vec<bool> = vec![false; len];
+  let mut components: Vec<Vec<usize>> = Vec::new();
+  for root in postorder.iter().rev().copied() {
+    if assigned[root] {
+      continue;
+    }
+    assigned[root] = true;
+    let mut component: Vec<usize> = Vec::new();
+    let mut stack: Vec<usize> = vec![root];
+    while let Some(node) = stack.pop() {
+      component.push(node);
+      for predecessor in predecessors[node].iter().copied() {
+        if !assigned[predecessor] {
+          assigned[predecessor] = true;
+          stack.push(predecessor);
+        }
+      }
+    }
+    component.sort_unstable();
+    components.push(component);
+  }
+  components.sort_by_key(|component| component[0]);
+  components
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg;
+  use crate::test_util::label;
+
+  #[test]
+  fn test_find_loops() {
+    // Stop; Play; If(-6); If(-12)
+    let input = [0x07, 0x06, 0x9d, 0x02, 0x00, 0xfa, 0xff, 0x9d, 0x02, 0x00, 0xf4, 0xff];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    let forest = find_loops(&graph);
+    assert_eq!(
+      forest.loops,
+      vec![
+        Loop {
+          headers: vec![label("l0_0")],
+          blocks: BTreeSet::from([label("l0_0"), label("l0_1"), label("l0_7")]),
+          latches: vec![label("l0_7")],
+          exits: vec![LoopExit {
+            from: label("l0_7"),
+            to: None,
+          }],
+          parent: None,
+          children: vec![1],
+          depth: 0,
+        },
+        Loop {
+          headers: vec![label("l0_1")],
+          blocks: BTreeSet::from([label("l0_1")]),
+          latches: vec![label("l0_1")],
+          exits: vec![LoopExit {
+            from: label("l0_1"),
+            to: Some(label("l0_7")),
+          }],
+          parent: Some(0),
+          children: Vec::new(),
+          depth: 1,
+        },
+      ]
+    );
+    assert_eq!(forest.roots().collect::<Vec<_>>(), [0]);
+    assert_eq!(forest.innermost(&label("l0_1")), Some(1));
+    assert_eq!(forest.innermost(&label("l0_7")), Some(0));
+    assert_eq!(forest.loop_depth(&label("l0_1")), 2);
+  }
+
+  #[test]
+  fn test_find_loops_irreducible() {
+    // If(+6); Play; Jump(+0); Stop; Jump(-12)
+    let input = [
+      0x9d, 0x02, 0x00, 0x06, 0x00, 0x06, 0x99, 0x02, 0x00, 0x00, 0x00, 0x07, 0x99, 0x02, 0x00, 0xf4, 0xff,
+    ];
+    let graph = FlowGraph::new(&parse_cfg(&input));
+    let forest = find_loops(&graph);
+    assert_eq!(forest.loops.len(), 1);
+    let irreducible = &forest.loops[0];
+    assert!(irreducible.is_irreducible());
+    assert_eq!(irreducible.headers, [label("l0_11"), label("l0_5")]);
+    assert_eq!(irreducible.latches, [label("l0_11"), label("l0_5")]);
+    assert_eq!(irreducible.exits, []);
+  }
+}
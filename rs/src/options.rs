@@ -0,0 +1,171 @@
+use crate::labels::LabelStyle;
+
+/// Configuration of the AVM1 parser.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+  /// Version of the SWF file containing the actions, `None` if unknown.
+  pub swf_version: Option<u8>,
+  /// Encoding used for strings that are not valid UTF-8.
+  ///
+  /// Since SWF 6, strings are always UTF-8. Older players use the encoding of the system locale
+  /// instead: strings of SWF files older than version 6 are decoded with this encoding. When the
+  /// version is unknown, strings that fail to decode as UTF-8 are decoded with this encoding
+  /// instead of producing an error.
+  pub fallback_encoding: Option<StringEncoding>,
+  /// Report actions whose body is longer than the fields they contain as errors.
+  ///
+  /// The Flash Player ignores the extra bytes, this is the default behavior.
+  pub strict: bool,
+  /// Keep the values decoded before the first value with an unknown type code of a `Push`
+  /// action, instead of replacing the whole action by an error.
+  ///
+  /// The undecodable tail is reported with `Diagnostic::PartialPush`. Other errors (truncated
+  /// values, invalid strings) still replace the action by an error.
+  pub recover_push: bool,
+  /// Report actions that the player for `swf_version` does not recognize, with
+  /// `Diagnostic::UnsupportedAction`.
+  ///
+  /// Ignored if `swf_version` is unknown.
+  pub check_versions: bool,
+  /// Handling of function, `try` and `with` bodies declaring a size larger than the available
+  /// input when building a CFG.
+  ///
+  /// These bodies are always reported with `Diagnostic::OversizedBody`.
+  pub oversized_bodies: OversizedBodyPolicy,
+  /// Keep the unreachable code in the CFG, as orphan blocks appended to the innermost CFG
+  /// (function, `try` or `with` body) containing them.
+  ///
+  /// These blocks are decoded linearly from the start of each unreachable range, and flagged as
+  /// unreachable in the source map.
+  ///
+  /// Not supported by lazy CFGs (`parse_cfg_lazy`): the unreachable code is dropped.
+  pub keep_unreachable: bool,
+  /// Naming scheme of the CFG labels.
+  ///
+  /// Not supported by lazy CFGs (`parse_cfg_lazy`): they always use the default
+  /// `LabelStyle::Layer`.
+  pub labels: LabelStyle,
+  /// Resource limits, used to bound the work done on untrusted input.
+  pub limits: ParseLimits,
+}
+
+/// Resource limits of the parser
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+  /// Maximum number of actions decoded when building a CFG, including nested functions.
+  ///
+  /// Actions reached once the limit is exhausted are replaced by an error flow.
+  pub max_actions: Option<usize>,
+  /// Maximum nesting depth of code blocks (function, `try` and `with` bodies) when building a CFG.
+  ///
+  /// Actions exceeding it are replaced by an error flow. Defaults to `DEFAULT_MAX_DEPTH`.
+  ///
+  /// The CFG parser recurses into nested blocks: with `None`, deeply nested input overflows the
+  /// stack. Larger limits require a larger stack.
+  pub max_depth: Option<usize>,
+}
+
+impl ParseLimits {
+  /// Default nesting depth limit.
+  ///
+  /// It fits in the 2 MiB stack of spawned threads, even in debug builds, and is far above the
+  /// nesting of code produced by compilers.
+  pub const DEFAULT_MAX_DEPTH: usize = 64;
+}
+
+impl Default for ParseLimits {
+  fn default() -> Self {
+    Self {
+      max_actions: None,
+      max_depth: Some(Self::DEFAULT_MAX_DEPTH),
+    }
+  }
+}
+
+/// Handling of code blocks larger than the input available for them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OversizedBodyPolicy {
+  /// Use the declared size: the actions past the end of the input are read as `End`.
+  Keep,
+  /// Shrink the body to the available input.
+  Clamp,
+  /// Replace the action by an error flow, caused by `ParseErrorKind::OversizedBody`.
+  Error,
+}
+
+impl Default for OversizedBodyPolicy {
+  fn default() -> Self {
+    OversizedBodyPolicy::Keep
+  }
+}
+
+impl ParseOptions {
+  /// Decodes the bytes of a string (without its nul terminator).
+  pub(crate) fn decode_string(&self, bytes: &[u8]) -> Option<String> {
+    match (self.swf_version, self.fallback_encoding) {
+      (Some(swf_version), Some(encoding)) if swf_version < 6 => encoding.decode(bytes),
+      (None, Some(encoding)) => match std::str::from_utf8(bytes) {
+        Ok(checked) => Some(checked.to_string()),
+        Err(_) => encoding.decode(bytes),
+      },
+      _ => std::str::from_utf8(bytes).ok().map(String::from),
+    }
+  }
+}
+
+/// Legacy string encodings used by pre-SWF6 content.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StringEncoding {
+  /// Western European encoding, superset of Latin-1
+  Windows1252,
+  /// Japanese encoding
+  ShiftJis,
+}
+
+impl StringEncoding {
+  fn decode(self, bytes: &[u8]) -> Option<String> {
+    let encoding: &'static encoding_rs::Encoding = match self {
+      StringEncoding::Windows1252 => encoding_rs::WINDOWS_1252,
+      StringEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+    };
+    encoding
+      .decode_without_bom_handling_and_without_replacement(bytes)
+      .map(|decoded| decoded.into_owned())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_decode_string() {
+    let latin1 = ParseOptions {
+      swf_version: Some(5),
+      fallback_encoding: Some(StringEncoding::Windows1252),
+      ..ParseOptions::default()
+    };
+    assert_eq!(latin1.decode_string(b"caf\xe9"), Some(String::from("café")));
+    // UTF-8 encoding of "é", also valid Windows-1252
+    assert_eq!(latin1.decode_string(b"\xc3\xa9"), Some(String::from("Ã©")));
+
+    let sjis = ParseOptions {
+      fallback_encoding: Some(StringEncoding::ShiftJis),
+      ..ParseOptions::default()
+    };
+    assert_eq!(
+      sjis.decode_string(b"\x83\x65\x83\x58\x83\x67"),
+      Some(String::from("テスト"))
+    );
+    assert_eq!(sjis.decode_string(b"\x83"), None);
+    assert_eq!(sjis.decode_string(b"\xc3\xa9"), Some(String::from("é")));
+
+    let swf6 = ParseOptions {
+      swf_version: Some(6),
+      ..latin1
+    };
+    assert_eq!(swf6.decode_string(b"caf\xe9"), None);
+    assert_eq!(swf6.decode_string(b"\xc3\xa9"), Some(String::from("é")));
+    assert_eq!(ParseOptions::default().decode_string(b"caf\xe9"), None);
+  }
+}
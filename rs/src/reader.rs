@@ -0,0 +1,266 @@
+use crate::avm1::{parse_action_header, parse_decoded_action, truncated_action_error, ActionTrivia};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::options::ParseOptions;
+use avm1_types::raw;
+
+/// Streaming reader over the actions of an AVM1 buffer.
+///
+/// Iterating over the reader yields `(offset, encoded_length, action)` tuples, starting from the
+/// current position and following the encoded actions linearly until the end of the buffer.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ActionReader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+  options: ParseOptions,
+}
+
+impl<'a> ActionReader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Self::with_options(bytes, ParseOptions::default())
+  }
+
+  pub fn with_options(bytes: &'a [u8], options: ParseOptions) -> Self {
+    ActionReader {
+      bytes,
+      offset: 0,
+      options,
+    }
+  }
+
+  /// Buffer read by this reader
+  pub fn bytes(&self) -> &'a [u8] {
+    self.bytes
+  }
+
+  /// Options used to parse the actions
+  pub fn options(&self) -> &ParseOptions {
+    &self.options
+  }
+
+  /// Current position of the reader
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  /// Moves the reader to `offset`; it does not have to be the start of an action.
+  pub fn seek(&mut self, offset: usize) {
+    self.offset = offset;
+  }
+
+  /// Reads the action at `offset` with its trivia and diagnostics, without moving the reader.
+  ///
+  /// Reading past the end of the buffer returns an implicit `End` action with a length of `0`.
+  /// A truncated action returns an `Error` action spanning the rest of the buffer, with an empty
+  /// trivia.
+  pub fn read_parts_at(&self, offset: usize) -> ActionParts {
+    if offset >= self.bytes.len() {
+      return ActionParts::new(0, raw::Action::End);
+    }
+    let input: &[u8] = &self.bytes[offset..];
+    match parse_decoded_action(input, offset, &self.options) {
+      Ok((next_input, decoded)) => ActionParts {
+        len: input.len() - next_input.len(),
+        trivia: decoded.trivia(),
+        action: decoded.action,
+        error: decoded.error,
+        diagnostics: decoded.diagnostics,
+      },
+      Err(_) => {
+        let error = truncated_action_error(input, offset);
+        ActionParts {
+          error: Some(error),
+          ..ActionParts::new(input.len(), raw::Action::Error(error.into()))
+        }
+      }
+    }
+  }
+
+  /// Returns the offset reached after skipping `count` actions from `offset`.
+  ///
+  /// Only the action headers are read. Returns `None` if the buffer ends in the middle of a
+  /// skipped action.
+  pub fn skip_from(&self, offset: usize, count: usize) -> Option<usize> {
+    match self.skip_partial_from(offset, count) {
+      (offset, skipped) if skipped == count => Some(offset),
+      _ => None,
+    }
+  }
+
+  /// Skips up to `count` actions from `offset`, stopping at the end of the buffer or before the
+  /// first truncated action.
+  ///
+  /// Returns the offset reached and the number of skipped actions.
+  pub fn skip_partial_from(&self, offset: usize, count: usize) -> (usize, usize) {
+    let mut input: &[u8] = match self.bytes.get(offset..) {
+      Some(input) => input,
+      None => return (offset, 0),
+    };
+    for skipped in 0..count {
+      let next_input = parse_action_header(input)
+        .ok()
+        .and_then(|(next_input, header)| next_input.get(header.length..));
+      match next_input {
+        Some(next_input) => input = next_input,
+        None => return (self.bytes.len() - input.len(), skipped),
+      }
+    }
+    (self.bytes.len() - input.len(), count)
+  }
+}
+
+/// Action read by `ActionReader::read_parts_at`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionParts {
+  /// Encoded length of the action (header and body)
+  pub len: usize,
+  pub action: raw::Action,
+  /// Cause of the error, if `action` is an `Error` action
+  pub error: Option<ParseError>,
+  /// Bits and bytes of the action not represented in `action`
+  pub trivia: ActionTrivia,
+  /// Diagnostics found while parsing the action
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ActionParts {
+  pub(crate) fn new(len: usize, action: raw::Action) -> Self {
+    Self {
+      len,
+      action,
+      error: None,
+      trivia: ActionTrivia::default(),
+      diagnostics: Vec::new(),
+    }
+  }
+}
+
+impl<'a> Iterator for ActionReader<'a> {
+  type Item = (usize, usize, raw::Action);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.offset >= self.bytes.len() {
+      return None;
+    }
+    let offset = self.offset;
+    let parts = self.read_parts_at(offset);
+    self.offset = offset + parts.len;
+    Some((offset, parts.len, parts.action))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::diagnostic::UnknownActionBehavior;
+  use crate::error::ParseErrorKind;
+  use crate::options::StringEncoding;
+  use avm1_types::PushValue;
+
+  #[test]
+  fn test_read_actions() {
+    let input = [0x06, 0x99, 0x02, 0x00, 0xfe, 0xff, 0x00];
+    let actual: Vec<(usize, usize, raw::Action)> = ActionReader::new(&input).collect();
+    let expected = vec![
+      (0, 1, raw::Action::Play),
+      (1, 5, raw::Action::Jump(raw::Jump { offset: -2 })),
+      (6, 1, raw::Action::End),
+    ];
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_seek() {
+    let input = [0x06, 0x07, 0x00];
+    let mut reader = ActionReader::new(&input);
+    reader.seek(1);
+    assert_eq!(reader.next(), Some((1, 1, raw::Action::Stop)));
+    assert_eq!(reader.offset(), 2);
+    assert_eq!(reader.read_parts_at(3), ActionParts::new(0, raw::Action::End));
+  }
+
+  #[test]
+  fn test_read_truncated() {
+    let input = [0x06, 0x96, 0x05, 0x00, 0x07];
+    let mut reader = ActionReader::new(&input);
+    assert_eq!(reader.next(), Some((0, 1, raw::Action::Play)));
+    assert!(matches!(reader.next(), Some((1, 4, raw::Action::Error(_)))));
+    assert_eq!(reader.next(), None);
+    let expected = ParseError {
+      offset: 1,
+      code: 0x96,
+      field: "body",
+      kind: ParseErrorKind::UnexpectedEnd,
+    };
+    assert_eq!(reader.read_parts_at(1).error, Some(expected));
+  }
+
+  #[test]
+  fn test_read_legacy_string() {
+    let input = [0x96, 0x06, 0x00, 0x00, 0x63, 0x61, 0x66, 0xe9, 0x00];
+    let options = ParseOptions {
+      swf_version: Some(5),
+      fallback_encoding: Some(StringEncoding::Windows1252),
+      ..ParseOptions::default()
+    };
+    let expected = raw::Action::Push(raw::Push {
+      values: vec![PushValue::String(String::from("café"))],
+    });
+    assert_eq!(
+      ActionReader::with_options(&input, options).read_parts_at(0),
+      ActionParts::new(9, expected)
+    );
+    assert!(matches!(
+      ActionReader::new(&input).read_parts_at(0),
+      ActionParts {
+        len: 9,
+        action: raw::Action::Error(_),
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn test_skip_from() {
+    let input = [0x06, 0x96, 0x02, 0x00, 0x02, 0x03, 0x07];
+    let reader = ActionReader::new(&input);
+    assert_eq!(reader.skip_from(0, 0), Some(0));
+    assert_eq!(reader.skip_from(0, 2), Some(6));
+    assert_eq!(reader.skip_from(0, 3), Some(7));
+    assert_eq!(reader.skip_from(0, 4), None);
+    assert_eq!(ActionReader::new(&input[1..4]).skip_from(0, 1), None);
+    assert_eq!(reader.skip_partial_from(0, 2), (6, 2));
+    assert_eq!(reader.skip_partial_from(1, 5), (7, 2));
+    assert_eq!(reader.skip_partial_from(8, 1), (8, 0));
+  }
+
+  #[test]
+  fn test_read_parts_unsupported() {
+    // `StrictEquals` (SWF 6), `Stop`
+    let input = [0x66, 0x07];
+    let options = ParseOptions {
+      swf_version: Some(5),
+      check_versions: true,
+      ..ParseOptions::default()
+    };
+    let reader = ActionReader::with_options(&input, options);
+    let expected = Diagnostic::UnsupportedAction {
+      offset: 0,
+      code: 0x66,
+      swf_version: 5,
+      min_version: Some(6),
+      behavior: UnknownActionBehavior::Skip,
+    };
+    let expected = ActionParts {
+      diagnostics: vec![expected],
+      ..ActionParts::new(1, raw::Action::StrictEquals)
+    };
+    assert_eq!(reader.read_parts_at(0), expected);
+    assert_eq!(reader.read_parts_at(1), ActionParts::new(1, raw::Action::Stop));
+    let unchecked = ActionReader::new(&input);
+    assert_eq!(
+      unchecked.read_parts_at(0),
+      ActionParts::new(1, raw::Action::StrictEquals)
+    );
+  }
+}
@@ -0,0 +1,245 @@
+use crate::graph::collect_blocks;
+use avm1_types::cfg;
+use avm1_types::cfg::{Cfg, CfgBlock, CfgFlow, CfgLabel};
+use avm1_types::{CatchTarget, FunctionFlags, PushValue};
+
+/// Number of global registers, used by the root code and `DefineFunction` bodies
+const GLOBAL_REGISTER_COUNT: u8 = 4;
+
+/// Register accesses of the root code or of a function body (without its nested functions)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterUsage {
+  /// Function definition, `None` for the root code
+  pub definition: Option<RegisterLocation>,
+  /// The scope has its own registers (`DefineFunction2` body), instead of the global registers
+  pub local: bool,
+  /// Number of available registers: the declared `register_count` for local registers, `4` for
+  /// the global registers
+  pub register_count: u8,
+  /// Accesses, in block order
+  pub accesses: Vec<RegisterAccess>,
+}
+
+impl RegisterUsage {
+  /// Returns the accesses to registers beyond `register_count`.
+  pub fn out_of_range(&self) -> impl Iterator<Item = &RegisterAccess> + '_ {
+    self
+      .accesses
+      .iter()
+      .filter(move |access| access.register >= self.register_count)
+  }
+}
+
+/// Action of a CFG
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterLocation {
+  pub label: CfgLabel,
+  /// Index of the action in the block, `actions.len()` for the flow of the block
+  pub action: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterAccess {
+  pub register: u8,
+  pub kind: RegisterAccessKind,
+  /// Action accessing the register: the `DefineFunction2` action for preloads and parameters, the
+  /// `Try` flow for catch targets
+  pub location: RegisterLocation,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegisterAccessKind {
+  /// `PushValue::Register`
+  Read,
+  /// `StoreRegister`
+  Write,
+  /// Value preloaded by a `DefineFunction2` flag
+  Preload(Preload),
+  /// Parameter of a `DefineFunction2`, by index
+  Parameter(usize),
+  /// Exception stored by a `catch` block
+  Catch,
+}
+
+/// Values preloaded in registers by `DefineFunction2`, in allocation order
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Preload {
+  This,
+  Arguments,
+  Super,
+  Root,
+  Parent,
+  Global,
+}
+
+/// Lists the register accesses of the root code and of each function body of the CFG.
+///
+/// The root code comes first, then the functions in depth-first order. Accesses beyond the
+/// `register_count` of a `DefineFunction2` are returned by `RegisterUsage::out_of_range`.
+pub fn register_usage(cfg: &Cfg) -> Vec<RegisterUsage> {
+  let mut usages: Vec<RegisterUsage> = Vec::new();
+  let root = RegisterUsage {
+    definition: None,
+    local: false,
+    register_count: GLOBAL_REGISTER_COUNT,
+    accesses: Vec::new(),
+  };
+  collect_usage(cfg, root, &mut usages);
+  usages
+}
+
+fn collect_usage(body: &Cfg, mut usage: RegisterUsage, usages: &mut Vec<RegisterUsage>) {
+  let mut blocks: Vec<&CfgBlock> = Vec::new();
+  collect_blocks(body, &mut blocks);
+
+  let mut functions: Vec<(&Cfg, RegisterUsage)> = Vec::new();
+  for block in blocks {
+    let location = |action: usize| RegisterLocation {
+      label: block.label.clone(),
+      action,
+    };
+    for (index, action) in block.actions.iter().enumerate() {
+      match action {
+        cfg::Action::Push(action) => {
+          for value in action.values.iter() {
+            if let PushValue::Register(register) = value {
+              usage.accesses.push(RegisterAccess {
+                register: *register,
+                kind: RegisterAccessKind::Read,
+                location: location(index),
+              });
+            }
+          }
+        }
+        cfg::Action::StoreRegister(action) => usage.accesses.push(RegisterAccess {
+          register: action.register,
+          kind: RegisterAccessKind::Write,
+          location: location(index),
+        }),
+        cfg::Action::DefineFunction(action) => {
+          let function = RegisterUsage {
+            definition: Some(location(index)),
+            local: false,
+            register_count: GLOBAL_REGISTER_COUNT,
+            accesses: Vec::new(),
+          };
+          functions.push((&action.body, function));
+        }
+        cfg::Action::DefineFunction2(action) => {
+          let function = RegisterUsage {
+            definition: Some(location(index)),
+            local: true,
+            register_count: action.register_count,
+            accesses: function_setup(action, location(index)),
+          };
+          functions.push((&action.body, function));
+        }
+        _ => {}
+      }
+    }
+    if let CfgFlow::Try(flow) = &block.flow {
+      if let Some(CatchTarget::Register(register)) = flow.catch.as_ref().map(|catch| &catch.target) {
+        usage.accesses.push(RegisterAccess {
+          register: *register,
+          kind: RegisterAccessKind::Catch,
+          location: location(block.actions.len()),
+        });
+      }
+    }
+  }
+
+  usages.push(usage);
+  for (body, function) in functions {
+    collect_usage(body, function, usages);
+  }
+}
+
+/// Returns the registers written when calling a `DefineFunction2` function: the preloaded values
+/// are allocated from register 1, then the parameters are stored in their register.
+fn function_setup(action: &cfg::DefineFunction2, location: RegisterLocation) -> Vec<RegisterAccess> {
+  let preloads = [
+    (FunctionFlags::PRELOAD_THIS, Preload::This),
+    (FunctionFlags::PRELOAD_ARGUMENTS, Preload::Arguments),
+    (FunctionFlags::PRELOAD_SUPER, Preload::Super),
+    (FunctionFlags::PRELOAD_ROOT, Preload::Root),
+    (FunctionFlags::PRELOAD_PARENT, Preload::Parent),
+    (FunctionFlags::PRELOAD_GLOBAL, Preload::Global),
+  ];
+  let mut accesses: Vec<RegisterAccess> = Vec::new();
+  let mut register: u8 = 1;
+  for (flag, preload) in preloads.iter() {
+    if action.flags.contains(*flag) {
+      accesses.push(RegisterAccess {
+        register,
+        kind: RegisterAccessKind::Preload(*preload),
+        location: location.clone(),
+      });
+      register += 1;
+    }
+  }
+  for (index, parameter) in action.parameters.iter().enumerate() {
+    // Parameters in register 0 are stored in a variable
+    if parameter.register != 0 {
+      accesses.push(RegisterAccess {
+        register: parameter.register,
+        kind: RegisterAccessKind::Parameter(index),
+        location: location.clone(),
+      });
+    }
+  }
+  accesses
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg;
+
+  #[test]
+  fn test_register_usage() {
+    // DefineFunction2("", [x: r3], register_count: 3, preload: this, _root) {
+    //   Push(r2); StoreRegister(5)
+    // }
+    // Push(r4)
+    let input = [
+      0x8e, 0x0b, 0x00, 0x00, 0x01, 0x00, 0x03, 0x41, 0x00, 0x03, 0x78, 0x00, 0x09, 0x00, 0x96, 0x02, 0x00, 0x04, 0x02,
+      0x87, 0x01, 0x00, 0x05, 0x96, 0x02, 0x00, 0x04, 0x04,
+    ];
+    let location = |label: &str, action: usize| RegisterLocation {
+      label: CfgLabel(String::from(label)),
+      action,
+    };
+    let access = |register: u8, kind: RegisterAccessKind, location: RegisterLocation| RegisterAccess {
+      register,
+      kind,
+      location,
+    };
+    let usages = register_usage(&parse_cfg(&input));
+    assert_eq!(
+      usages,
+      [
+        RegisterUsage {
+          definition: None,
+          local: false,
+          register_count: 4,
+          accesses: vec![access(4, RegisterAccessKind::Read, location("l0_0", 1))],
+        },
+        RegisterUsage {
+          definition: Some(location("l0_0", 0)),
+          local: true,
+          register_count: 3,
+          accesses: vec![
+            access(1, RegisterAccessKind::Preload(Preload::This), location("l0_0", 0)),
+            access(2, RegisterAccessKind::Preload(Preload::Root), location("l0_0", 0)),
+            access(3, RegisterAccessKind::Parameter(0), location("l0_0", 0)),
+            access(2, RegisterAccessKind::Read, location("l1_14", 0)),
+            access(5, RegisterAccessKind::Write, location("l1_14", 1)),
+          ],
+        },
+      ]
+    );
+    let out_of_range: Vec<u8> = usages[1].out_of_range().map(|access| access.register).collect();
+    assert_eq!(out_of_range, [3, 5]);
+    assert_eq!(usages[0].out_of_range().count(), 1);
+  }
+}
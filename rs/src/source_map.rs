@@ -0,0 +1,151 @@
+use avm1_types::cfg::CfgLabel;
+use core::ops::Range;
+use std::collections::BTreeMap;
+
+/// Byte ranges of the blocks of a CFG (including the blocks of nested CFGs) in the parsed input
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+  pub blocks: BTreeMap<CfgLabel, BlockSpan>,
+  /// Pairs of decoded actions sharing bytes, sorted by the start of the first action.
+  ///
+  /// Overlapping actions are produced by jumps into the middle of an action: the same bytes are
+  /// decoded in two ways.
+  pub overlaps: Vec<Overlap>,
+}
+
+impl SourceMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn block(&self, label: &CfgLabel) -> Option<&BlockSpan> {
+    self.blocks.get(label)
+  }
+
+  /// Returns the label of the block containing the action starting at `offset`, if any.
+  pub fn find_action(&self, offset: usize) -> Option<&CfgLabel> {
+    self.blocks.iter().find_map(|(label, span)| {
+      let is_action = span.actions.iter().any(|action| action.start == offset);
+      let is_flow = span.flow.as_ref().map_or(false, |flow| flow.start == offset);
+      if is_action || is_flow {
+        Some(label)
+      } else {
+        None
+      }
+    })
+  }
+
+  /// Returns the ranges of `0..len` not covered by any action, sorted by offset.
+  pub fn uncovered(&self, len: usize) -> Vec<Range<usize>> {
+    let mut covered: Vec<&Range<usize>> = self
+      .blocks
+      .values()
+      .flat_map(|block| block.actions.iter().chain(block.flow.iter()))
+      .collect();
+    covered.sort_by_key(|range| range.start);
+
+    let mut uncovered: Vec<Range<usize>> = Vec::new();
+    let mut start: usize = 0;
+    for range in covered {
+      if range.start > start {
+        uncovered.push(start..range.start.min(len));
+      }
+      start = start.max(range.end);
+    }
+    if start < len {
+      uncovered.push(start..len);
+    }
+    uncovered.retain(|range| !range.is_empty());
+    uncovered
+  }
+
+  /// Renames the labels of the blocks, see `rename_labels`.
+  pub(crate) fn rename(&mut self, names: &BTreeMap<CfgLabel, CfgLabel>) {
+    let rename = |label: &CfgLabel| names.get(label).unwrap_or(label).clone();
+    self.blocks = self
+      .blocks
+      .iter()
+      .map(|(label, block)| (rename(label), block.clone()))
+      .collect();
+    for overlap in self.overlaps.iter_mut() {
+      overlap.first.label = rename(&overlap.first.label);
+      overlap.second.label = rename(&overlap.second.label);
+    }
+  }
+
+  /// Finds the overlapping actions and marks their blocks.
+  pub(crate) fn detect_overlaps(&mut self) {
+    let mut spans: Vec<ActionSpan> = Vec::new();
+    for (label, block) in self.blocks.iter() {
+      for range in block.actions.iter().chain(block.flow.iter()) {
+        spans.push(ActionSpan {
+          label: label.clone(),
+          range: range.clone(),
+        });
+      }
+    }
+    spans.sort_by(|left, right| {
+      (left.range.start, left.range.end, &left.label).cmp(&(right.range.start, right.range.end, &right.label))
+    });
+
+    let mut overlaps: Vec<Overlap> = Vec::new();
+    let mut active: Vec<&ActionSpan> = Vec::new();
+    for span in spans.iter() {
+      active.retain(|other| other.range.end > span.range.start);
+      for other in active.iter() {
+        // Actions starting at the same offset are the same decoding, reached from different blocks.
+        if other.range.start != span.range.start {
+          overlaps.push(Overlap {
+            first: (*other).clone(),
+            second: span.clone(),
+          });
+        }
+      }
+      active.push(span);
+    }
+
+    for overlap in overlaps.iter() {
+      for span in [&overlap.first, &overlap.second] {
+        if let Some(block) = self.blocks.get_mut(&span.label) {
+          block.overlapping = true;
+        }
+      }
+    }
+    self.overlaps = overlaps;
+  }
+}
+
+/// Byte ranges of a CFG block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockSpan {
+  /// Range from the first action of the block to the end of its last action.
+  ///
+  /// It includes the bodies of the functions defined in the block.
+  pub range: Range<usize>,
+  /// Range of each action of the block (header and fields, without the function bodies)
+  pub actions: Vec<Range<usize>>,
+  /// Range of the action providing the flow of the block, `None` if the flow is implicit (the
+  /// block falls through to the next block, leaves its parent range or hits the action limit)
+  pub flow: Option<Range<usize>>,
+  /// The block contains an action overlapping another decoded action
+  pub overlapping: bool,
+  /// The block is an orphan block, not reachable from the entry point (see
+  /// `ParseOptions::keep_unreachable`)
+  pub unreachable: bool,
+}
+
+/// Decoded action, identified by its block and byte range
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionSpan {
+  pub label: CfgLabel,
+  pub range: Range<usize>,
+}
+
+/// Two decoded actions sharing bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overlap {
+  /// Action starting first
+  pub first: ActionSpan,
+  /// Action starting inside `first`
+  pub second: ActionSpan,
+}
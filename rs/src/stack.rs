@@ -0,0 +1,285 @@
+use crate::convert::{convert_action, FromRawActionError};
+use avm1_types::cfg;
+use avm1_types::cfg::CfgFlow;
+use avm1_types::raw;
+
+/// Number of values popped or pushed by an action
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StackCount {
+  Fixed(usize),
+  /// `fixed` values, plus `factor` values per unit of the count read from the stack.
+  ///
+  /// The count is the popped value at `count_index` (`0` for the top of the stack). For example,
+  /// `CallFunction` pops the function name and the argument count, then the arguments:
+  /// `Counted { fixed: 2, count_index: 1, factor: 1 }`.
+  Counted {
+    fixed: usize,
+    count_index: usize,
+    factor: usize,
+  },
+  /// `fixed` values, plus `extra` values if the popped value at `flag_index` is true.
+  ///
+  /// For example, `StartDrag` pops the constraint rectangle only if its third value is true:
+  /// `Conditional { fixed: 3, flag_index: 2, extra: 4 }`.
+  Conditional {
+    fixed: usize,
+    flag_index: usize,
+    extra: usize,
+  },
+  /// At least `min` values, the exact number is only known at runtime (`Enumerate` pushes the
+  /// properties of an object, unknown actions have an unknown effect).
+  Dynamic {
+    min: usize,
+  },
+}
+
+impl StackCount {
+  /// Returns the minimum number of values.
+  pub fn min(self) -> usize {
+    match self {
+      StackCount::Fixed(count) => count,
+      StackCount::Counted { fixed, .. } => fixed,
+      StackCount::Conditional { fixed, .. } => fixed,
+      StackCount::Dynamic { min } => min,
+    }
+  }
+
+  /// Returns the number of values, if it does not depend on the execution.
+  pub fn fixed(self) -> Option<usize> {
+    match self {
+      StackCount::Fixed(count) => Some(count),
+      _ => None,
+    }
+  }
+}
+
+/// Values popped from the stack, then pushed on the stack by an action
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StackEffect {
+  pub pops: StackCount,
+  pub pushes: StackCount,
+}
+
+impl StackEffect {
+  const fn fixed(pops: usize, pushes: usize) -> Self {
+    Self {
+      pops: StackCount::Fixed(pops),
+      pushes: StackCount::Fixed(pushes),
+    }
+  }
+
+  const fn counted(fixed: usize, count_index: usize, factor: usize, pushes: usize) -> Self {
+    Self {
+      pops: StackCount::Counted {
+        fixed,
+        count_index,
+        factor,
+      },
+      pushes: StackCount::Fixed(pushes),
+    }
+  }
+
+  const UNKNOWN: Self = Self {
+    pops: StackCount::Dynamic { min: 0 },
+    pushes: StackCount::Dynamic { min: 0 },
+  };
+
+  /// Returns the change of the stack height, if it does not depend on the execution.
+  pub fn delta(self) -> Option<isize> {
+    let pops = isize::try_from(self.pops.fixed()?).ok()?;
+    let pushes = isize::try_from(self.pushes.fixed()?).ok()?;
+    Some(pushes - pops)
+  }
+}
+
+/// Returns the stack effect of a CFG action.
+///
+/// Function definitions push the function only if it is anonymous. Unknown actions (`Raw`) have an
+/// unknown effect.
+pub fn stack_effect(action: &cfg::Action) -> StackEffect {
+  match action {
+    cfg::Action::DefineFunction(action) => StackEffect::fixed(0, usize::from(action.name.is_empty())),
+    cfg::Action::DefineFunction2(action) => StackEffect::fixed(0, usize::from(action.name.is_empty())),
+    cfg::Action::Push(action) => StackEffect::fixed(0, action.values.len()),
+    cfg::Action::Raw(_) => StackEffect::UNKNOWN,
+
+    cfg::Action::ConstantPool(_)
+    | cfg::Action::EndDrag
+    | cfg::Action::GetUrl(_)
+    | cfg::Action::GotoFrame(_)
+    | cfg::Action::GotoLabel(_)
+    | cfg::Action::NextFrame
+    | cfg::Action::Play
+    | cfg::Action::PrevFrame
+    | cfg::Action::SetTarget(_)
+    | cfg::Action::Stop
+    | cfg::Action::StopSounds
+    | cfg::Action::StrictMode(_)
+    | cfg::Action::ToggleQuality => StackEffect::fixed(0, 0),
+
+    cfg::Action::GetTime => StackEffect::fixed(0, 1),
+
+    cfg::Action::Call
+    | cfg::Action::DefineLocal2
+    | cfg::Action::GotoFrame2(_)
+    | cfg::Action::Pop
+    | cfg::Action::RemoveSprite
+    | cfg::Action::SetTarget2
+    | cfg::Action::Trace => StackEffect::fixed(1, 0),
+
+    cfg::Action::AsciiToChar
+    | cfg::Action::CharToAscii
+    | cfg::Action::Decrement
+    | cfg::Action::Delete2
+    | cfg::Action::GetVariable
+    | cfg::Action::Increment
+    | cfg::Action::MbAsciiToChar
+    | cfg::Action::MbCharToAscii
+    | cfg::Action::MbStringLength
+    | cfg::Action::Not
+    | cfg::Action::RandomNumber
+    | cfg::Action::StoreRegister(_)
+    | cfg::Action::StringLength
+    | cfg::Action::TargetPath
+    | cfg::Action::ToInteger
+    | cfg::Action::ToNumber
+    | cfg::Action::ToString
+    | cfg::Action::TypeOf => StackEffect::fixed(1, 1),
+
+    cfg::Action::PushDuplicate => StackEffect::fixed(1, 2),
+
+    cfg::Action::DefineLocal | cfg::Action::Extends | cfg::Action::GetUrl2(_) | cfg::Action::SetVariable => {
+      StackEffect::fixed(2, 0)
+    }
+
+    cfg::Action::Add
+    | cfg::Action::Add2
+    | cfg::Action::And
+    | cfg::Action::BitAnd
+    | cfg::Action::BitLShift
+    | cfg::Action::BitOr
+    | cfg::Action::BitRShift
+    | cfg::Action::BitURShift
+    | cfg::Action::BitXor
+    | cfg::Action::CastOp
+    | cfg::Action::Delete
+    | cfg::Action::Divide
+    | cfg::Action::Equals
+    | cfg::Action::Equals2
+    | cfg::Action::GetMember
+    | cfg::Action::GetProperty
+    | cfg::Action::Greater
+    | cfg::Action::InstanceOf
+    | cfg::Action::Less
+    | cfg::Action::Less2
+    | cfg::Action::Modulo
+    | cfg::Action::Multiply
+    | cfg::Action::Or
+    | cfg::Action::StrictEquals
+    | cfg::Action::StringAdd
+    | cfg::Action::StringEquals
+    | cfg::Action::StringGreater
+    | cfg::Action::StringLess
+    | cfg::Action::Subtract => StackEffect::fixed(2, 1),
+
+    cfg::Action::StackSwap => StackEffect::fixed(2, 2),
+
+    cfg::Action::CloneSprite | cfg::Action::SetMember | cfg::Action::SetProperty => StackEffect::fixed(3, 0),
+
+    cfg::Action::MbStringExtract | cfg::Action::StringExtract => StackEffect::fixed(3, 1),
+
+    cfg::Action::Enumerate | cfg::Action::Enumerate2 => StackEffect {
+      pops: StackCount::Fixed(1),
+      pushes: StackCount::Dynamic { min: 1 },
+    },
+
+    cfg::Action::FsCommand2 => StackEffect::counted(1, 0, 1, 1),
+    cfg::Action::InitArray => StackEffect::counted(1, 0, 1, 1),
+    cfg::Action::InitObject => StackEffect::counted(1, 0, 2, 1),
+    cfg::Action::CallFunction => StackEffect::counted(2, 1, 1, 1),
+    cfg::Action::ImplementsOp => StackEffect::counted(2, 1, 1, 0),
+    cfg::Action::NewObject => StackEffect::counted(2, 1, 1, 1),
+    cfg::Action::CallMethod => StackEffect::counted(3, 2, 1, 1),
+    cfg::Action::NewMethod => StackEffect::counted(3, 2, 1, 1),
+    cfg::Action::StartDrag => StackEffect {
+      pops: StackCount::Conditional {
+        fixed: 3,
+        flag_index: 2,
+        extra: 4,
+      },
+      pushes: StackCount::Fixed(0),
+    },
+  }
+}
+
+/// Returns the stack effect of a raw action, see `stack_effect`.
+///
+/// The actions represented by a `CfgFlow` in CFGs have the same effect as with `flow_stack_effect`.
+pub fn raw_stack_effect(action: &raw::Action) -> StackEffect {
+  match convert_action(action.clone()) {
+    Ok(action) => stack_effect(&action),
+    Err(FromRawActionError::DefineFunction(action)) => StackEffect::fixed(0, usize::from(action.name.is_empty())),
+    Err(FromRawActionError::DefineFunction2(action)) => StackEffect::fixed(0, usize::from(action.name.is_empty())),
+    Err(FromRawActionError::Error(_)) => StackEffect::UNKNOWN,
+    Err(FromRawActionError::End)
+    | Err(FromRawActionError::Jump(_))
+    | Err(FromRawActionError::Try(_))
+    | Err(FromRawActionError::WaitForFrame(_)) => StackEffect::fixed(0, 0),
+    Err(FromRawActionError::If(_))
+    | Err(FromRawActionError::Return)
+    | Err(FromRawActionError::Throw)
+    | Err(FromRawActionError::WaitForFrame2(_))
+    | Err(FromRawActionError::With(_)) => StackEffect::fixed(1, 0),
+  }
+}
+
+/// Returns the stack effect of the action ending a CFG block.
+///
+/// The effect of the nested CFGs (`Try` and `With` bodies) is not included: `With` pops the
+/// target object before entering its body.
+pub fn flow_stack_effect(flow: &CfgFlow) -> StackEffect {
+  match flow {
+    CfgFlow::Error(_) => StackEffect::UNKNOWN,
+    CfgFlow::Simple(_) | CfgFlow::Try(_) | CfgFlow::WaitForFrame(_) => StackEffect::fixed(0, 0),
+    CfgFlow::If(_) | CfgFlow::Return | CfgFlow::Throw | CfgFlow::WaitForFrame2(_) | CfgFlow::With(_) => {
+      StackEffect::fixed(1, 0)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use avm1_types::action;
+  use avm1_types::PushValue;
+
+  #[test]
+  fn test_stack_effect() {
+    assert_eq!(stack_effect(&cfg::Action::Add).delta(), Some(-1));
+    assert_eq!(stack_effect(&cfg::Action::PushDuplicate), StackEffect::fixed(1, 2));
+    let push = action::Push {
+      values: vec![PushValue::Null, PushValue::Undefined],
+    };
+    assert_eq!(stack_effect(&cfg::Action::Push(push.clone())).delta(), Some(2));
+    assert_eq!(raw_stack_effect(&raw::Action::Push(push)).delta(), Some(2));
+    assert_eq!(
+      stack_effect(&cfg::Action::CallMethod).pops,
+      StackCount::Counted {
+        fixed: 3,
+        count_index: 2,
+        factor: 1
+      }
+    );
+    assert_eq!(stack_effect(&cfg::Action::Enumerate2).delta(), None);
+    assert_eq!(stack_effect(&cfg::Action::Enumerate2).pushes.min(), 1);
+    assert_eq!(
+      raw_stack_effect(&raw::Action::Return),
+      flow_stack_effect(&CfgFlow::Return)
+    );
+    assert_eq!(
+      raw_stack_effect(&raw::Action::StartDrag),
+      stack_effect(&cfg::Action::StartDrag)
+    );
+    assert_eq!(stack_effect(&cfg::Action::StartDrag).pops.min(), 3);
+  }
+}
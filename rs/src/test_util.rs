@@ -0,0 +1,7 @@
+//! Helpers shared by the unit tests
+
+use avm1_types::cfg::CfgLabel;
+
+pub(crate) fn label(name: &str) -> CfgLabel {
+  CfgLabel(String::from(name))
+}
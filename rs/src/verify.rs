@@ -0,0 +1,352 @@
+use crate::graph::{collect_blocks, function_bodies, FlowGraph};
+use crate::stack::{flow_stack_effect, stack_effect, StackCount, StackEffect};
+use avm1_types::cfg;
+use avm1_types::cfg::{Cfg, CfgBlock, CfgFlow, CfgLabel};
+use avm1_types::PushValue;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Result of the stack height verification of a CFG
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackReport {
+  /// Stack height at the start of each reachable block, `None` if it depends on the execution.
+  ///
+  /// Function bodies start with an empty stack.
+  pub heights: BTreeMap<CfgLabel, Option<usize>>,
+  pub issues: Vec<StackIssue>,
+}
+
+/// Stack height anomaly, tolerated by the player
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackIssue {
+  /// A block is reached with different stack heights.
+  InconsistentHeight {
+    label: CfgLabel,
+    /// Predecessor reaching the block with the height `actual`
+    from: CfgLabel,
+    /// Height of the first path reaching the block
+    expected: usize,
+    actual: usize,
+  },
+  /// An action pops more values than available: the player pops `undefined` instead.
+  Underflow {
+    label: CfgLabel,
+    /// Index of the action in the block, `actions.len()` for the flow of the block
+    action: usize,
+    height: usize,
+    pops: usize,
+  },
+  /// A `try` or `with` region is left with a stack height different from its entry height.
+  UnbalancedRegion {
+    kind: RegionKind,
+    /// Block ending with the `Try` or `With` flow
+    label: CfgLabel,
+    /// Block of the region jumping out of it
+    exit: CfgLabel,
+    expected: usize,
+    actual: usize,
+  },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+  Try,
+  With,
+}
+
+/// Propagates the stack heights through the CFG (including nested functions) and reports the
+/// inconsistencies.
+///
+/// Counts read from the stack (`CallFunction`, `InitArray`, ...) are resolved when they are pushed
+/// as literals in the same block; otherwise the height becomes unknown until the next merge with a
+/// known height.
+pub fn verify_stack(cfg: &Cfg) -> StackReport {
+  let mut report = StackReport::default();
+  verify_function(cfg, &mut report);
+  report
+}
+
+struct Region<'a> {
+  kind: RegionKind,
+  owner: &'a CfgLabel,
+}
+
+/// `try` and `with` regions of a function body
+struct Regions<'a> {
+  regions: Vec<Region<'a>>,
+  /// Regions enclosing each block, outermost first
+  enclosing: HashMap<&'a CfgLabel, Vec<usize>>,
+}
+
+/// Abstract stack at a point of a block
+#[derive(Clone)]
+struct StackState {
+  /// `None` if unknown
+  height: Option<usize>,
+  /// Integer values pushed in the current block, top last (`None` for unknown values)
+  constants: Vec<Option<i64>>,
+}
+
+fn verify_function(body: &Cfg, report: &mut StackReport) {
+  let graph = FlowGraph::new(body);
+  let mut blocks: Vec<&CfgBlock> = Vec::new();
+  collect_blocks(body, &mut blocks);
+  let blocks: HashMap<&CfgLabel, &CfgBlock> = blocks.into_iter().map(|block| (&block.label, block)).collect();
+  let mut regions = Regions {
+    regions: Vec::new(),
+    enclosing: HashMap::new(),
+  };
+  collect_regions(body, &mut Vec::new(), &mut regions);
+  let mut region_heights: HashMap<&CfgLabel, Option<usize>> = HashMap::new();
+
+  let mut heights: HashMap<CfgLabel, Option<usize>> = HashMap::new();
+  let mut queue: VecDeque<CfgLabel> = VecDeque::new();
+  heights.insert(graph.entry().clone(), Some(0));
+  queue.push_back(graph.entry().clone());
+  while let Some(label) = queue.pop_front() {
+    let block = blocks[&label];
+    let mut state = StackState {
+      height: heights[&label],
+      constants: Vec::new(),
+    };
+    for (index, action) in block.actions.iter().enumerate() {
+      apply(&mut state, &stack_effect(action), action, &label, index, report);
+    }
+    apply_flow(&mut state, &block.flow, &label, block.actions.len(), report);
+    let height = state.height;
+    if matches!(block.flow, CfgFlow::Try(_) | CfgFlow::With(_)) {
+      region_heights.insert(&block.label, height);
+    }
+
+    let enclosing: &[usize] = &regions.enclosing[&label];
+    for target in graph.successors(&label) {
+      // Regions are nested: the edge leaves the regions after the common prefix, innermost first
+      let target_enclosing: &[usize] = &regions.enclosing[target];
+      let common = enclosing
+        .iter()
+        .zip(target_enclosing.iter())
+        .take_while(|(region, target_region)| region == target_region)
+        .count();
+      for region in enclosing[common..].iter().rev().map(|region| &regions.regions[*region]) {
+        if let (Some(Some(expected)), Some(actual)) = (region_heights.get(region.owner), height) {
+          if *expected != actual {
+            report.issues.push(StackIssue::UnbalancedRegion {
+              kind: region.kind,
+              label: region.owner.clone(),
+              exit: label.clone(),
+              expected: *expected,
+              actual,
+            });
+          }
+        }
+      }
+
+      match heights.get(target).copied() {
+        None => {
+          heights.insert(target.clone(), height);
+          queue.push_back(target.clone());
+        }
+        Some(None) if height.is_some() => {
+          heights.insert(target.clone(), height);
+          queue.push_back(target.clone());
+        }
+        Some(Some(expected)) => match height {
+          Some(actual) if actual != expected => report.issues.push(StackIssue::InconsistentHeight {
+            label: target.clone(),
+            from: label.clone(),
+            expected,
+            actual,
+          }),
+          _ => {}
+        },
+        Some(None) => {}
+      }
+    }
+  }
+  report.heights.extend(heights);
+
+  for function in function_bodies(body) {
+    verify_function(function, report);
+  }
+}
+
+/// Collects the regions of `body` and of its nested `try` and `with` bodies, `enclosing` being the
+/// regions enclosing `body`.
+fn collect_regions<'a>(body: &'a Cfg, enclosing: &mut Vec<usize>, regions: &mut Regions<'a>) {
+  for block in body.blocks.iter() {
+    regions.enclosing.insert(&block.label, enclosing.clone());
+    let (kind, bodies): (RegionKind, Vec<&Cfg>) = match &block.flow {
+      CfgFlow::Try(flow) => {
+        let mut bodies: Vec<&Cfg> = vec![&flow.r#try];
+        bodies.extend(flow.catch.as_ref().map(|catch| &catch.body));
+        bodies.extend(flow.finally.as_ref());
+        (RegionKind::Try, bodies)
+      }
+      CfgFlow::With(flow) => (RegionKind::With, vec![&flow.body]),
+      _ => continue,
+    };
+    enclosing.push(regions.regions.len());
+    regions.regions.push(Region {
+      kind,
+      owner: &block.label,
+    });
+    for body in bodies {
+      collect_regions(body, enclosing, regions);
+    }
+    enclosing.pop();
+  }
+}
+
+fn apply(
+  state: &mut StackState,
+  effect: &StackEffect,
+  action: &cfg::Action,
+  label: &CfgLabel,
+  index: usize,
+  report: &mut StackReport,
+) {
+  let pops: Option<usize> = match effect.pops {
+    StackCount::Fixed(count) => Some(count),
+    StackCount::Counted {
+      fixed,
+      count_index,
+      factor,
+    } => popped_constant(state, count_index)
+      .and_then(|count| usize::try_from(count).ok())
+      .and_then(|count| count.checked_mul(factor))
+      .and_then(|count| count.checked_add(fixed)),
+    StackCount::Conditional {
+      fixed,
+      flag_index,
+      extra,
+    } => popped_constant(state, flag_index).map(|flag| if flag != 0 { fixed + extra } else { fixed }),
+    StackCount::Dynamic { .. } => None,
+  };
+  if let Some(height) = state.height {
+    let needed = pops.unwrap_or_else(|| effect.pops.min());
+    if height < needed {
+      report.issues.push(StackIssue::Underflow {
+        label: label.clone(),
+        action: index,
+        height,
+        pops: needed,
+      });
+    }
+  }
+  state.height = match (state.height, pops, effect.pushes.fixed()) {
+    (Some(height), Some(pops), Some(pushes)) => Some(height.saturating_sub(pops) + pushes),
+    _ => None,
+  };
+
+  let duplicate = match (action, state.constants.last()) {
+    (cfg::Action::PushDuplicate, Some(top)) => *top,
+    _ => None,
+  };
+  match pops {
+    Some(pops) => {
+      let len = state.constants.len().saturating_sub(pops);
+      state.constants.truncate(len);
+    }
+    None => state.constants.clear(),
+  }
+  match action {
+    cfg::Action::Push(push) => state.constants.extend(push.values.iter().map(push_integer)),
+    cfg::Action::PushDuplicate => state.constants.extend([duplicate, duplicate]),
+    _ => match effect.pushes.fixed() {
+      Some(pushes) => state.constants.extend(std::iter::repeat(None).take(pushes)),
+      None => state.constants.clear(),
+    },
+  }
+}
+
+fn apply_flow(state: &mut StackState, flow: &CfgFlow, label: &CfgLabel, index: usize, report: &mut StackReport) {
+  let effect = flow_stack_effect(flow);
+  let pops = effect.pops.min();
+  if let Some(height) = state.height {
+    if height < pops {
+      report.issues.push(StackIssue::Underflow {
+        label: label.clone(),
+        action: index,
+        height,
+        pops,
+      });
+    }
+  }
+  state.height = match (state.height, effect.delta()) {
+    (Some(height), Some(_)) => Some(height.saturating_sub(pops)),
+    _ => None,
+  };
+}
+
+/// Returns the integer constant at `index` from the top of the stack (`0` for the top), if it was
+/// pushed in the current block.
+fn popped_constant(state: &StackState, index: usize) -> Option<i64> {
+  let position = state.constants.len().checked_sub(index.checked_add(1)?)?;
+  state.constants[position]
+}
+
+fn push_integer(value: &PushValue) -> Option<i64> {
+  match value {
+    PushValue::Boolean(value) => Some(i64::from(*value)),
+    PushValue::Sint32(value) => Some(i64::from(*value)),
+    PushValue::Float64(value) if value.fract() == 0.0 && value.abs() <= f64::from(i32::MAX) => Some(*value as i64),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cfg::parse_cfg;
+  use crate::test_util::label;
+
+  #[test]
+  fn test_verify_stack() {
+    // Push(0, "f"); CallFunction; Pop; Pop
+    let input = [
+      0x96, 0x08, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x00, 0x3d, 0x17, 0x17,
+    ];
+    let report = verify_stack(&parse_cfg(&input));
+    assert_eq!(report.heights, BTreeMap::from([(label("l0_0"), Some(0))]));
+    assert_eq!(
+      report.issues,
+      [StackIssue::Underflow {
+        label: label("l0_0"),
+        action: 3,
+        height: 0,
+        pops: 1,
+      }]
+    );
+
+    // Push(null); If(+4); Push(null); Stop
+    let input = [
+      0x96, 0x01, 0x00, 0x02, 0x9d, 0x02, 0x00, 0x04, 0x00, 0x96, 0x01, 0x00, 0x02, 0x07,
+    ];
+    let report = verify_stack(&parse_cfg(&input));
+    assert_eq!(
+      report.issues,
+      [StackIssue::InconsistentHeight {
+        label: label("l0_13"),
+        from: label("l0_9"),
+        expected: 0,
+        actual: 1,
+      }]
+    );
+
+    // Push(null); With { Push(null) }; Stop
+    let input = [
+      0x96, 0x01, 0x00, 0x02, 0x94, 0x02, 0x00, 0x04, 0x00, 0x96, 0x01, 0x00, 0x02, 0x07,
+    ];
+    let report = verify_stack(&parse_cfg(&input));
+    assert_eq!(report.heights[&label("l0_13")], Some(1));
+    assert_eq!(
+      report.issues,
+      [StackIssue::UnbalancedRegion {
+        kind: RegionKind::With,
+        label: label("l0_0"),
+        exit: label("l1_9"),
+        expected: 0,
+        actual: 1,
+      }]
+    );
+  }
+}
@@ -0,0 +1,78 @@
+use crate::diagnostic::{Diagnostic, UnknownActionBehavior};
+
+/// Returns the first SWF version whose player recognizes the action `code`.
+///
+/// Returns `None` for codes that are not recognized by any version of the desktop player: undefined
+/// codes, and actions only supported by Flash Lite (`FsCommand2`).
+pub fn action_swf_version(code: u8) -> Option<u8> {
+  match code {
+    0x00 | 0x04..=0x09 | 0x81 | 0x83 | 0x8a..=0x8c => Some(3),
+    0x0a..=0x15 | 0x17 | 0x18 | 0x1c | 0x1d | 0x20..=0x29 | 0x30..=0x37 => Some(4),
+    0x8d | 0x96 | 0x99 | 0x9a | 0x9d..=0x9f => Some(4),
+    0x3a..=0x53 | 0x60..=0x65 | 0x87 | 0x88 | 0x94 | 0x9b => Some(5),
+    0x54 | 0x55 | 0x66..=0x68 => Some(6),
+    0x2a..=0x2c | 0x69 | 0x89 | 0x8e | 0x8f => Some(7),
+    _ => None,
+  }
+}
+
+/// Describes how a player ignoring the action `code` continues the execution.
+///
+/// Unknown actions are skipped using their length (one byte for codes below `0x80`), but the
+/// length of the actions holding code blocks does not cover these blocks: they are then executed
+/// as if they were inlined after the action.
+pub fn unknown_action_behavior(code: u8) -> UnknownActionBehavior {
+  match code {
+    0x8e | 0x8f | 0x94 | 0x9b => UnknownActionBehavior::InlineBody,
+    _ => UnknownActionBehavior::Skip,
+  }
+}
+
+/// Returns a diagnostic if the action `code` is not recognized by the player for `swf_version`.
+pub(crate) fn check_action_version(offset: usize, code: u8, swf_version: u8) -> Option<Diagnostic> {
+  let min_version = action_swf_version(code);
+  match min_version {
+    Some(min_version) if min_version <= swf_version => None,
+    _ => Some(Diagnostic::UnsupportedAction {
+      offset,
+      code,
+      swf_version,
+      min_version,
+      behavior: unknown_action_behavior(code),
+    }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_action_version() {
+    // `Stop`
+    assert_eq!(check_action_version(0, 0x07, 3), None);
+    // `Try` in SWF 6
+    assert_eq!(
+      check_action_version(4, 0x8f, 6),
+      Some(Diagnostic::UnsupportedAction {
+        offset: 4,
+        code: 0x8f,
+        swf_version: 6,
+        min_version: Some(7),
+        behavior: UnknownActionBehavior::InlineBody,
+      })
+    );
+    assert_eq!(check_action_version(4, 0x8f, 7), None);
+    // Undefined code
+    assert_eq!(
+      check_action_version(0, 0x01, 10),
+      Some(Diagnostic::UnsupportedAction {
+        offset: 0,
+        code: 0x01,
+        swf_version: 10,
+        min_version: None,
+        behavior: UnknownActionBehavior::Skip,
+      })
+    );
+  }
+}